@@ -0,0 +1,995 @@
+//! Verification of the in-toto attestations `cosign attest` attaches to
+//! plugin images, such as SLSA provenance and SBOMs.
+
+use crate::Cli;
+use crate::config::{AttestationPolicy, VerificationConfig};
+use crate::oci::{
+    IdentityVerifier, PublicKeysVerifier, Signers, SigstoreTrust, TrustRootCell, cli_identities,
+    setup_trust_repository,
+};
+use anyhow::{Context, Result, anyhow};
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use oci_client::{Client, Reference, secrets::RegistryAuth};
+use rustls_pki_types::{CertificateDer, UnixTime};
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use sigstore::bundle::verify::VerificationPolicy;
+use sigstore::cosign::signature_layers::CertificateSubject;
+use sigstore::crypto::{CosignVerificationKey, Signature, SigningScheme};
+use sigstore::trust::TrustRoot;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+use x509_cert::{
+    Certificate,
+    der::{Decode, DecodePem, Encode, asn1::Utf8StringRef},
+};
+
+const DSSE_MEDIA_TYPE: &str = "application/vnd.dsse.envelope.v1+json";
+const IN_TOTO_PAYLOAD_TYPE: &str = "application/vnd.in-toto+json";
+const CERTIFICATE_ANNOTATION: &str = "dev.sigstore.cosign/certificate";
+const CHAIN_ANNOTATION: &str = "dev.sigstore.cosign/chain";
+const BUNDLE_ANNOTATION: &str = "dev.sigstore.cosign/bundle";
+
+const PROVENANCE_PREDICATE_TYPE: &str = "https://slsa.dev/provenance/";
+const SBOM_PREDICATE_TYPES: [&str; 2] = ["https://spdx.dev/Document", "https://cyclonedx.org/bom"];
+
+/// The Fulcio certificate extension holding the source repository of a CI
+/// workflow's identity, such as GitHub Actions'.
+const SOURCE_REPOSITORY_OID: &str = "1.3.6.1.4.1.57264.1.12";
+
+/// The DER contents of the code signing extended key usage OID, which
+/// Fulcio certificates carry.
+const CODE_SIGNING_EKU: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x03];
+
+/// Who has to have signed an attestation.
+enum Signer<'a> {
+    PublicKeys(PublicKeysVerifier),
+    Keyless(IdentityVerifier, &'a SigstoreTrust),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Envelope {
+    payload_type: String,
+    payload: String,
+    signatures: Vec<EnvelopeSignature>,
+}
+
+#[derive(Deserialize)]
+struct EnvelopeSignature {
+    sig: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Statement {
+    #[serde(default)]
+    subject: Vec<Subject>,
+    predicate_type: String,
+    #[serde(default)]
+    predicate: Value,
+    /// For keyless attestations, the source repository the signing
+    /// certificate was issued for, or its subject.
+    #[serde(skip)]
+    signed_from: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Subject {
+    #[serde(default)]
+    digest: HashMap<String, String>,
+}
+
+/// DSSE's pre-authentication encoding of a payload, which is what an
+/// envelope's signatures sign.
+fn pae(payload_type: &str, payload: &[u8]) -> Vec<u8> {
+    let mut pae = format!(
+        "DSSEv1 {} {payload_type} {} ",
+        payload_type.len(),
+        payload.len()
+    )
+    .into_bytes();
+    pae.extend_from_slice(payload);
+    pae
+}
+
+/// The builder id of SLSA v1 or v0.2 provenance.
+fn builder_id(predicate: &Value) -> Option<&str> {
+    ["/runDetails/builder/id", "/builder/id"]
+        .iter()
+        .find_map(|pointer| predicate.pointer(pointer)?.as_str())
+}
+
+/// The source repository of SLSA v1 or v0.2 provenance, without the `git+`
+/// prefix of its URI.
+fn source_repo(predicate: &Value) -> Option<&str> {
+    [
+        "/buildDefinition/externalParameters/workflow/repository",
+        "/buildDefinition/resolvedDependencies/0/uri",
+        "/invocation/configSource/uri",
+    ]
+    .iter()
+    .find_map(|pointer| predicate.pointer(pointer)?.as_str())
+    .map(|uri| uri.trim_start_matches("git+"))
+}
+
+/// Checks the verified attestations of an image against the policy.
+fn check_policy(policy: &AttestationPolicy, statements: &[Statement]) -> Result<()> {
+    let require_provenance = policy.provenance == Some(true)
+        || policy.builder_id.is_some()
+        || policy.source_repo_prefix.is_some();
+    if require_provenance {
+        let provenance: Vec<_> = statements
+            .iter()
+            .filter(|statement| {
+                statement
+                    .predicate_type
+                    .starts_with(PROVENANCE_PREDICATE_TYPE)
+            })
+            .map(|statement| {
+                (
+                    builder_id(&statement.predicate),
+                    source_repo(&statement.predicate),
+                    statement.signed_from.as_deref(),
+                )
+            })
+            .collect();
+        if provenance.is_empty() {
+            return Err(anyhow!("No signed SLSA provenance attestation found"));
+        }
+        // A keyless signer can claim any source, so it has to be in the
+        // expected repositories itself.
+        let satisfied = provenance.iter().any(|(builder, source, signed_from)| {
+            policy
+                .builder_id
+                .as_ref()
+                .is_none_or(|expected| *builder == Some(expected.as_str()))
+                && policy.source_repo_prefix.as_ref().is_none_or(|prefix| {
+                    source.is_some_and(|source| source.starts_with(prefix))
+                        && signed_from.is_none_or(|signed_from| signed_from.starts_with(prefix))
+                })
+        });
+        if !satisfied {
+            let found = provenance
+                .iter()
+                .map(|(builder, source, signed_from)| {
+                    let signed_from = signed_from
+                        .map(|signed_from| format!(" signed from {signed_from}"))
+                        .unwrap_or_default();
+                    format!(
+                        "builder {} from {}{signed_from}",
+                        builder.unwrap_or("unknown"),
+                        source.unwrap_or("unknown source")
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            return Err(anyhow!(
+                "No SLSA provenance matches the attestation policy, found {found}"
+            ));
+        }
+    }
+
+    let has_sbom = statements.iter().any(|statement| {
+        SBOM_PREDICATE_TYPES
+            .iter()
+            .any(|predicate_type| statement.predicate_type.starts_with(predicate_type))
+    });
+    if policy.sbom == Some(true) && !has_sbom {
+        return Err(anyhow!("No signed SBOM attestation found"));
+    }
+    Ok(())
+}
+
+/// Checks that a keyless attestation's certificate was issued by Fulcio to
+/// one of the identities while it was valid, as recorded by Rekor, and that
+/// it signed the envelope. Returns the source repository the certificate was
+/// issued for, or its subject when it names none.
+fn verify_certificate(
+    trust_root: &SigstoreTrust,
+    identities: &IdentityVerifier,
+    annotations: &BTreeMap<String, String>,
+    payload: &[u8],
+    pae: &[u8],
+    signatures: &[Vec<u8>],
+) -> Result<String> {
+    let certificate = annotations
+        .get(CERTIFICATE_ANNOTATION)
+        .context("No signing certificate")?;
+    let certificate = Certificate::from_pem(certificate).context("Invalid signing certificate")?;
+
+    // The Rekor entry says when the envelope was signed, and is bound to it
+    // by the payload hash.
+    let bundle: sigstore::cosign::bundle::Bundle = serde_json::from_str(
+        annotations
+            .get(BUNDLE_ANNOTATION)
+            .context("Not recorded in the transparency log")?,
+    )
+    .context("Invalid transparency log bundle")?;
+    let signed_entry: BTreeMap<_, Value> = BTreeMap::from([
+        ("body", bundle.payload.body.clone().into()),
+        ("integratedTime", bundle.payload.integrated_time.into()),
+        ("logID", bundle.payload.log_id.clone().into()),
+        ("logIndex", bundle.payload.log_index.into()),
+    ]);
+    let signed_entry = serde_json::to_vec(&signed_entry)?;
+    // The key of the log the entry names, or else any Rekor key that was in
+    // use when the entry was logged.
+    let mut rekor_keys: Vec<_> = trust_root
+        .all_rekor_keys()
+        .iter()
+        .filter(|key| key.is_valid_at(bundle.payload.integrated_time))
+        .collect();
+    rekor_keys.sort_by_key(|key| key.log_id() != bundle.payload.log_id);
+    let logged_by_rekor = rekor_keys.iter().any(|key| {
+        CosignVerificationKey::from_der(&key.key, &SigningScheme::default()).is_ok_and(|key| {
+            key.verify_signature(
+                Signature::Base64Encoded(bundle.signed_entry_timestamp.as_bytes()),
+                &signed_entry,
+            )
+            .is_ok()
+        })
+    });
+    if !logged_by_rekor {
+        return Err(anyhow!("Invalid transparency log signature"));
+    }
+    let body: Value = serde_json::from_slice(&BASE64.decode(&bundle.payload.body)?)?;
+    let payload_hash = hex::encode(Sha256::digest(payload));
+    if body.pointer("/spec/content/payloadHash/value") != Some(&Value::from(payload_hash)) {
+        return Err(anyhow!(
+            "The transparency log entry is for another attestation"
+        ));
+    }
+
+    let anchors = trust_root
+        .fulcio_certs()?
+        .iter()
+        .map(|certificate| Ok(webpki::anchor_from_trusted_cert(certificate)?.to_owned()))
+        .collect::<Result<Vec<_>, webpki::Error>>()?;
+    let intermediates = annotations
+        .get(CHAIN_ANNOTATION)
+        .map(|chain| Certificate::load_pem_chain(chain.as_bytes()))
+        .transpose()
+        .context("Invalid certificate chain")?
+        .unwrap_or_default()
+        .iter()
+        .map(|certificate| Ok(CertificateDer::from(certificate.to_der()?)))
+        .collect::<Result<Vec<_>, x509_cert::der::Error>>()?;
+    let der = CertificateDer::from(certificate.to_der()?);
+    let integrated_time = u64::try_from(bundle.payload.integrated_time)?;
+    webpki::EndEntityCert::try_from(&der)?
+        .verify_for_usage(
+            webpki::ALL_VERIFICATION_ALGS,
+            &anchors,
+            &intermediates,
+            UnixTime::since_unix_epoch(Duration::from_secs(integrated_time)),
+            webpki::KeyUsage::required(CODE_SIGNING_EKU),
+            None,
+            None,
+        )
+        .context("The signing certificate is not trusted")?;
+
+    identities.verify(&certificate)?;
+
+    let key =
+        CosignVerificationKey::try_from(&certificate.tbs_certificate.subject_public_key_info)?;
+    if !signatures
+        .iter()
+        .any(|signature| key.verify_signature(Signature::Raw(signature), pae).is_ok())
+    {
+        return Err(anyhow!("Not signed by the signing certificate"));
+    }
+
+    let source_repository = certificate
+        .tbs_certificate
+        .extensions
+        .iter()
+        .flatten()
+        .find(|extension| extension.extn_id.to_string() == SOURCE_REPOSITORY_OID)
+        .and_then(|extension| Utf8StringRef::from_der(extension.extn_value.as_bytes()).ok())
+        .map(|repository| repository.to_string());
+    match source_repository {
+        Some(repository) => Ok(repository),
+        None => match CertificateSubject::from_certificate(&certificate)? {
+            CertificateSubject::Email(subject) | CertificateSubject::Uri(subject) => Ok(subject),
+        },
+    }
+}
+
+/// Verifies one attestation layer and returns its in-toto statement, which
+/// must be about the image with `image_digest`.
+fn verify_envelope(
+    signer: &Signer,
+    annotations: &BTreeMap<String, String>,
+    envelope: &[u8],
+    image_digest: &str,
+) -> Result<Statement> {
+    let envelope: Envelope = serde_json::from_slice(envelope).context("Invalid DSSE envelope")?;
+    if envelope.payload_type != IN_TOTO_PAYLOAD_TYPE {
+        return Err(anyhow!(
+            "Unsupported payload type {}",
+            envelope.payload_type
+        ));
+    }
+    let payload = BASE64.decode(&envelope.payload)?;
+    let pae = pae(&envelope.payload_type, &payload);
+    let signatures = envelope
+        .signatures
+        .iter()
+        .map(|signature| BASE64.decode(&signature.sig))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let signed_from = match signer {
+        Signer::PublicKeys(public_keys) => {
+            if !signatures
+                .iter()
+                .any(|signature| public_keys.verify_signature(&pae, signature))
+            {
+                return Err(anyhow!("Not signed by the configured public keys"));
+            }
+            None
+        }
+        Signer::Keyless(identities, trust_root) => Some(verify_certificate(
+            trust_root,
+            identities,
+            annotations,
+            &payload,
+            &pae,
+            &signatures,
+        )?),
+    };
+
+    let mut statement: Statement =
+        serde_json::from_slice(&payload).context("Invalid in-toto statement")?;
+    statement.signed_from = signed_from;
+    let image_hash = image_digest.trim_start_matches("sha256:");
+    if !statement
+        .subject
+        .iter()
+        .any(|subject| subject.digest.get("sha256").map(String::as_str) == Some(image_hash))
+    {
+        return Err(anyhow!("The attestation is about another image"));
+    }
+    Ok(statement)
+}
+
+/// Fetches the attestations `cosign attest` stored for an image, verifies
+/// them with the same signers as the image signature, and checks the
+/// verified ones against the policy. Attestations that don't verify are
+/// ignored.
+pub async fn verify_attestations(
+    cli: &Cli,
+    client: &Client,
+    trust_root: &TrustRootCell,
+    reference: &Reference,
+    auth: &RegistryAuth,
+    verification: &VerificationConfig,
+) -> Result<()> {
+    let Some(policy) = &verification.attestations else {
+        return Ok(());
+    };
+
+    let signer = match Signers::new(cli, Some(verification))? {
+        Signers::PublicKeys(public_keys) => Signer::PublicKeys(public_keys),
+        signers => {
+            let identities = match signers {
+                Signers::Identities(identities) => identities,
+                _ => {
+                    // Without an identity any Fulcio certificate is accepted,
+                    // and anyone could sign provenance naming the builder.
+                    let identities = cli_identities(cli);
+                    if (policy.builder_id.is_some() || policy.source_repo_prefix.is_some())
+                        && identities.iter().any(|identity| identity.subject == ".*")
+                    {
+                        return Err(anyhow!(
+                            "builder_id and source_repo_prefix need keyless attestations to be pinned to a signer, set identities in the verification block or --cert-email/--cert-url"
+                        ));
+                    }
+                    IdentityVerifier::new(&identities)?
+                }
+            };
+            let trust_root = trust_root
+                .get_or_try_init(|| setup_trust_repository(cli))
+                .await?;
+            Signer::Keyless(identities, trust_root)
+        }
+    };
+
+    let image_digest = client.fetch_manifest_digest(reference, auth).await?;
+    let attestations_reference = Reference::with_tag(
+        reference.registry().to_string(),
+        reference.repository().to_string(),
+        format!("{}.att", image_digest.replace(':', "-")),
+    );
+    let (manifest, _) = client
+        .pull_image_manifest(&attestations_reference, auth)
+        .await
+        .with_context(|| format!("No attestations found for {reference}"))?;
+
+    let mut statements = Vec::new();
+    for layer in manifest
+        .layers
+        .iter()
+        .filter(|layer| layer.media_type == DSSE_MEDIA_TYPE)
+    {
+        let mut envelope = Vec::new();
+        client
+            .pull_blob(&attestations_reference, layer, &mut envelope)
+            .await?;
+        let annotations = layer.annotations.clone().unwrap_or_default();
+        match verify_envelope(&signer, &annotations, &envelope, &image_digest) {
+            Ok(statement) => {
+                log::info!(
+                    "Verified {} attestation for {reference}",
+                    statement.predicate_type
+                );
+                statements.push(statement);
+            }
+            Err(e) => log::warn!(
+                "Ignoring attestation {} of {reference}: {e:#}",
+                layer.digest
+            ),
+        }
+    }
+
+    check_policy(policy, &statements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{oci::tests::push_test_image, test_registry::TestRegistry};
+    use clap::Parser;
+    use oci_client::client::{ClientConfig, ClientProtocol};
+    use oci_client::manifest;
+    use serde_json::json;
+    use sigstore::crypto::signing_key::SigStoreSigner;
+
+    fn statement(predicate_type: &str, predicate: Value) -> Statement {
+        serde_json::from_value(json!({
+            "_type": "https://in-toto.io/Statement/v1",
+            "subject": [],
+            "predicateType": predicate_type,
+            "predicate": predicate
+        }))
+        .unwrap()
+    }
+
+    fn slsa_v1(builder: &str, repository: &str) -> Statement {
+        statement(
+            "https://slsa.dev/provenance/v1",
+            json!({
+                "buildDefinition": {
+                    "externalParameters": { "workflow": { "repository": repository } }
+                },
+                "runDetails": { "builder": { "id": builder } }
+            }),
+        )
+    }
+
+    const GITHUB_BUILDER: &str = "https://github.com/actions/runner/github-hosted";
+
+    #[test]
+    fn test_check_policy_provenance() {
+        let policy = AttestationPolicy {
+            builder_id: Some(GITHUB_BUILDER.to_string()),
+            source_repo_prefix: Some("https://github.com/tuananh/".to_string()),
+            ..Default::default()
+        };
+        let statements = [slsa_v1(
+            GITHUB_BUILDER,
+            "https://github.com/tuananh/hyper-mcp",
+        )];
+        assert!(check_policy(&policy, &statements).is_ok());
+
+        let statements = [slsa_v1(
+            GITHUB_BUILDER,
+            "https://github.com/other/hyper-mcp",
+        )];
+        let error = check_policy(&policy, &statements).unwrap_err();
+        assert!(
+            error.to_string().contains("No SLSA provenance matches"),
+            "{error}"
+        );
+        assert!(
+            error
+                .to_string()
+                .contains("https://github.com/other/hyper-mcp")
+        );
+
+        // A keyless signer has to be in the expected repositories too.
+        let mut keyless = slsa_v1(GITHUB_BUILDER, "https://github.com/tuananh/hyper-mcp");
+        keyless.signed_from = Some("https://github.com/other/fork".to_string());
+        let error = check_policy(&policy, &[keyless]).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("signed from https://github.com/other/fork"),
+            "{error}"
+        );
+        let mut keyless = slsa_v1(GITHUB_BUILDER, "https://github.com/tuananh/hyper-mcp");
+        keyless.signed_from = Some("https://github.com/tuananh/hyper-mcp".to_string());
+        assert!(check_policy(&policy, &[keyless]).is_ok());
+
+        // SLSA v0.2 keeps the builder and source elsewhere.
+        let v02 = statement(
+            "https://slsa.dev/provenance/v0.2",
+            json!({
+                "builder": { "id": GITHUB_BUILDER },
+                "invocation": {
+                    "configSource": { "uri": "git+https://github.com/tuananh/hyper-mcp@refs/heads/main" }
+                }
+            }),
+        );
+        assert!(check_policy(&policy, &[v02]).is_ok());
+
+        // A builder or source constraint implies provenance is required.
+        let error = check_policy(&policy, &[]).unwrap_err();
+        assert!(error.to_string().contains("No signed SLSA provenance"));
+        assert!(check_policy(&AttestationPolicy::default(), &[]).is_ok());
+    }
+
+    #[test]
+    fn test_check_policy_sbom() {
+        let policy = AttestationPolicy {
+            sbom: Some(true),
+            ..Default::default()
+        };
+        let error = check_policy(&policy, &[slsa_v1(GITHUB_BUILDER, "")]).unwrap_err();
+        assert!(error.to_string().contains("No signed SBOM"));
+        assert!(
+            check_policy(
+                &policy,
+                &[statement("https://spdx.dev/Document", json!({}))]
+            )
+            .is_ok()
+        );
+        assert!(
+            check_policy(
+                &policy,
+                &[statement("https://cyclonedx.org/bom/v1.5", json!({}))]
+            )
+            .is_ok()
+        );
+    }
+
+    /// Attests statements like `cosign attest --key` does, storing them in
+    /// the registry next to the image.
+    fn attest_image(
+        registry: &TestRegistry,
+        repository: &str,
+        image_digest: &str,
+        statements: &[(&SigStoreSigner, Value)],
+    ) {
+        let config = registry.push_blob(b"{}");
+        let layers: Vec<_> = statements
+            .iter()
+            .map(|(signer, statement)| {
+                let payload = serde_json::to_vec(statement).unwrap();
+                let signature = signer.sign(&pae(IN_TOTO_PAYLOAD_TYPE, &payload)).unwrap();
+                let envelope = serde_json::to_vec(&json!({
+                    "payloadType": IN_TOTO_PAYLOAD_TYPE,
+                    "payload": BASE64.encode(&payload),
+                    "signatures": [{ "keyid": "", "sig": BASE64.encode(signature) }]
+                }))
+                .unwrap();
+                json!({
+                    "mediaType": DSSE_MEDIA_TYPE,
+                    "digest": registry.push_blob(&envelope),
+                    "size": envelope.len()
+                })
+            })
+            .collect();
+        registry.push_manifest(
+            repository,
+            &format!("{}.att", image_digest.replace(':', "-")),
+            manifest::OCI_IMAGE_MEDIA_TYPE,
+            &json!({
+                "schemaVersion": 2,
+                "mediaType": manifest::OCI_IMAGE_MEDIA_TYPE,
+                "config": {
+                    "mediaType": manifest::IMAGE_CONFIG_MEDIA_TYPE,
+                    "digest": config,
+                    "size": 2
+                },
+                "layers": layers
+            }),
+        );
+    }
+
+    fn provenance(image_digest: &str, repository: &str) -> Value {
+        json!({
+            "_type": "https://in-toto.io/Statement/v1",
+            "subject": [{
+                "name": "plugin",
+                "digest": { "sha256": image_digest.trim_start_matches("sha256:") }
+            }],
+            "predicateType": "https://slsa.dev/provenance/v1",
+            "predicate": {
+                "buildDefinition": {
+                    "externalParameters": { "workflow": { "repository": repository } }
+                },
+                "runDetails": { "builder": { "id": GITHUB_BUILDER } }
+            }
+        })
+    }
+
+    /// The Fulcio extension with the source repository, a DER UTF8String.
+    struct SourceRepository(&'static str);
+
+    impl x509_cert::der::oid::AssociatedOid for SourceRepository {
+        const OID: x509_cert::der::oid::ObjectIdentifier =
+            x509_cert::der::oid::ObjectIdentifier::new_unwrap(SOURCE_REPOSITORY_OID);
+    }
+
+    impl Encode for SourceRepository {
+        fn encoded_len(&self) -> x509_cert::der::Result<x509_cert::der::Length> {
+            Utf8StringRef::new(self.0)?.encoded_len()
+        }
+
+        fn encode(&self, writer: &mut impl x509_cert::der::Writer) -> x509_cert::der::Result<()> {
+            Utf8StringRef::new(self.0)?.encode(writer)
+        }
+    }
+
+    impl x509_cert::ext::AsExtension for SourceRepository {
+        fn critical(&self, _: &x509_cert::name::Name, _: &[x509_cert::ext::Extension]) -> bool {
+            false
+        }
+    }
+
+    /// A code signing certificate for `subject` issued by the `ca` root,
+    /// valid from `not_before` for ten minutes, like Fulcio's.
+    fn issue_certificate(
+        ca: &(p256::ecdsa::SigningKey, Certificate),
+        key: &p256::ecdsa::SigningKey,
+        subject: &str,
+        not_before: std::time::SystemTime,
+    ) -> Certificate {
+        use x509_cert::builder::{Builder, CertificateBuilder, Profile};
+        use x509_cert::der::asn1::Ia5String;
+        use x509_cert::der::oid::db::rfc5280::ID_KP_CODE_SIGNING;
+        use x509_cert::ext::pkix::{ExtendedKeyUsage, SubjectAltName, name::GeneralName};
+        use x509_cert::spki::SubjectPublicKeyInfoOwned;
+        use x509_cert::time::Validity;
+
+        let validity = Validity {
+            not_before: not_before.try_into().unwrap(),
+            not_after: (not_before + Duration::from_secs(600)).try_into().unwrap(),
+        };
+        let mut builder = CertificateBuilder::new(
+            Profile::Leaf {
+                issuer: ca.1.tbs_certificate.subject.clone(),
+                enable_key_agreement: false,
+                enable_key_encipherment: false,
+            },
+            x509_cert::serial_number::SerialNumber::from(2u32),
+            validity,
+            Default::default(),
+            SubjectPublicKeyInfoOwned::from_key(*key.verifying_key()).unwrap(),
+            &ca.0,
+        )
+        .unwrap();
+        builder
+            .add_extension(&SubjectAltName(vec![
+                GeneralName::UniformResourceIdentifier(Ia5String::new(subject).unwrap()),
+            ]))
+            .unwrap();
+        builder
+            .add_extension(&ExtendedKeyUsage(vec![ID_KP_CODE_SIGNING]))
+            .unwrap();
+        builder
+            .add_extension(&SourceRepository("https://github.com/org/plugin"))
+            .unwrap();
+        builder.build::<p256::ecdsa::DerSignature>().unwrap()
+    }
+
+    /// A throwaway root CA standing in for Fulcio's.
+    fn test_ca() -> (p256::ecdsa::SigningKey, Certificate) {
+        use std::str::FromStr;
+        use x509_cert::builder::{Builder, CertificateBuilder, Profile};
+        use x509_cert::spki::SubjectPublicKeyInfoOwned;
+        use x509_cert::time::Validity;
+
+        let key = p256::ecdsa::SigningKey::random(&mut p256::elliptic_curve::rand_core::OsRng);
+        let certificate = CertificateBuilder::new(
+            Profile::Root,
+            x509_cert::serial_number::SerialNumber::from(1u32),
+            Validity::from_now(Duration::from_secs(3600)).unwrap(),
+            x509_cert::name::Name::from_str("CN=Test Fulcio").unwrap(),
+            SubjectPublicKeyInfoOwned::from_key(*key.verifying_key()).unwrap(),
+            &key,
+        )
+        .unwrap()
+        .build::<p256::ecdsa::DerSignature>()
+        .unwrap();
+        (key, certificate)
+    }
+
+    /// The Rekor bundle `rekor` signs for an entry of `payload` logged at
+    /// `integrated_time`.
+    fn rekor_bundle(
+        rekor: &p256::ecdsa::SigningKey,
+        payload: &[u8],
+        integrated_time: i64,
+    ) -> String {
+        use p256::ecdsa::signature::Signer as _;
+        use p256::pkcs8::EncodePublicKey;
+
+        let body = BASE64.encode(
+            serde_json::to_vec(&json!({
+                "kind": "intoto",
+                "spec": {
+                    "content": {
+                        "payloadHash": {
+                            "algorithm": "sha256",
+                            "value": hex::encode(Sha256::digest(payload))
+                        }
+                    }
+                }
+            }))
+            .unwrap(),
+        );
+        let log_id = hex::encode(Sha256::digest(
+            rekor
+                .verifying_key()
+                .to_public_key_der()
+                .unwrap()
+                .as_bytes(),
+        ));
+        let signed_entry = json!({
+            "body": body,
+            "integratedTime": integrated_time,
+            "logID": log_id,
+            "logIndex": 1
+        });
+        let signature: p256::ecdsa::DerSignature =
+            rekor.sign(&serde_json::to_vec(&signed_entry).unwrap());
+        json!({
+            "SignedEntryTimestamp": BASE64.encode(signature.as_bytes()),
+            "Payload": signed_entry
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_verify_certificate() {
+        use crate::config::SigningIdentity;
+        use crate::oci::SigstoreTrust;
+        use p256::ecdsa::signature::Signer as _;
+        use p256::pkcs8::EncodePublicKey;
+        use sigstore::trust::ManualTrustRoot;
+        use std::time::{SystemTime, UNIX_EPOCH};
+        use x509_cert::der::{EncodePem, pem::LineEnding};
+
+        let random_key =
+            || p256::ecdsa::SigningKey::random(&mut p256::elliptic_curve::rand_core::OsRng);
+        let ca = test_ca();
+        let rekor = random_key();
+        let rekor_der = |key: &p256::ecdsa::SigningKey| {
+            key.verifying_key()
+                .to_public_key_der()
+                .unwrap()
+                .as_bytes()
+                .to_vec()
+        };
+        // Another log's key comes first, the entry's log id picks the right
+        // one.
+        let trust_root = SigstoreTrust::new(ManualTrustRoot {
+            fulcio_certs: vec![ca.1.to_der().unwrap().into()],
+            rekor_keys: vec![rekor_der(&random_key()), rekor_der(&rekor)],
+            ctfe_keys: Vec::new(),
+        });
+        let identities = |subject: &str| {
+            IdentityVerifier::new(&[SigningIdentity {
+                issuer: None,
+                subject: subject.to_string(),
+            }])
+            .unwrap()
+        };
+
+        let now = SystemTime::now();
+        let integrated_time = now.duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+        let payload = br#"{"predicateType":"https://slsa.dev/provenance/v1"}"#;
+        let pae = pae(IN_TOTO_PAYLOAD_TYPE, payload);
+        let key = random_key();
+        let signature: p256::ecdsa::DerSignature = key.sign(&pae);
+        let signatures = [signature.as_bytes().to_vec()];
+        let annotations = |certificate: &Certificate, bundle: String| {
+            BTreeMap::from([
+                (
+                    CERTIFICATE_ANNOTATION.to_string(),
+                    certificate.to_pem(LineEnding::LF).unwrap(),
+                ),
+                (BUNDLE_ANNOTATION.to_string(), bundle),
+            ])
+        };
+        let workflow = "https://github.com/org/plugin/.github/workflows/release.yml@refs/tags/v1";
+        let certificate = issue_certificate(&ca, &key, workflow, now - Duration::from_secs(60));
+        let verify = |identities: &IdentityVerifier, annotations: &BTreeMap<String, String>| {
+            verify_certificate(
+                &trust_root,
+                identities,
+                annotations,
+                payload,
+                &pae,
+                &signatures,
+            )
+        };
+
+        let valid = annotations(&certificate, rekor_bundle(&rekor, payload, integrated_time));
+        assert_eq!(
+            verify(&identities("https://github.com/org/plugin/.+"), &valid).unwrap(),
+            "https://github.com/org/plugin"
+        );
+
+        let error = verify(&identities("https://github.com/other/.+"), &valid).unwrap_err();
+        assert!(
+            format!("{error:#}").contains("signing identity"),
+            "{error:#}"
+        );
+
+        let other_payload = annotations(
+            &certificate,
+            rekor_bundle(&rekor, b"another attestation", integrated_time),
+        );
+        let error = verify(&identities(".*"), &other_payload).unwrap_err();
+        assert!(
+            error.to_string().contains("for another attestation"),
+            "{error:#}"
+        );
+
+        let untrusted_log = annotations(
+            &certificate,
+            rekor_bundle(&random_key(), payload, integrated_time),
+        );
+        let error = verify(&identities(".*"), &untrusted_log).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("Invalid transparency log signature"),
+            "{error:#}"
+        );
+
+        // Logged after the certificate expired.
+        let expired = issue_certificate(&ca, &key, workflow, now - Duration::from_secs(3600));
+        let expired = annotations(&expired, rekor_bundle(&rekor, payload, integrated_time));
+        let error = verify(&identities(".*"), &expired).unwrap_err();
+        assert!(error.to_string().contains("not trusted"), "{error:#}");
+    }
+
+    #[tokio::test]
+    async fn test_verify_attestations_keyless_needs_identity() {
+        let verification = VerificationConfig {
+            attestations: Some(AttestationPolicy {
+                builder_id: Some(GITHUB_BUILDER.to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let reference: Reference = "localhost:1/org/plugin:latest".parse().unwrap();
+        let error = verify_attestations(
+            &Cli::parse_from(["hyper-mcp"]),
+            &Client::default(),
+            &TrustRootCell::new(),
+            &reference,
+            &RegistryAuth::Anonymous,
+            &verification,
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            error.to_string().contains("pinned to a signer"),
+            "{error:#}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verify_attestations_with_public_keys() {
+        let registry = TestRegistry::start(None).await;
+        let image_digest = push_test_image(&registry, "org/plugin");
+        let signer = SigningScheme::ECDSA_P256_SHA256_ASN1
+            .create_signer()
+            .unwrap();
+        let other_signer = SigningScheme::ECDSA_P256_SHA256_ASN1
+            .create_signer()
+            .unwrap();
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let public_key = temp_dir.path().join("cosign.pub");
+        std::fs::write(
+            &public_key,
+            signer
+                .to_sigstore_keypair()
+                .unwrap()
+                .public_key_to_pem()
+                .unwrap(),
+        )
+        .unwrap();
+
+        let client = Client::new(ClientConfig {
+            protocol: ClientProtocol::Http,
+            ..Default::default()
+        });
+        let reference: Reference = format!("{}/org/plugin:latest", registry.address)
+            .parse()
+            .unwrap();
+        let verification = |source_repo_prefix: &str| VerificationConfig {
+            public_keys: Some(vec![public_key.clone()]),
+            attestations: Some(AttestationPolicy {
+                source_repo_prefix: Some(source_repo_prefix.to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let verify = |verification: VerificationConfig| {
+            let client = &client;
+            let reference = &reference;
+            async move {
+                verify_attestations(
+                    &Cli::parse_from(["hyper-mcp"]),
+                    client,
+                    &TrustRootCell::new(),
+                    reference,
+                    &RegistryAuth::Anonymous,
+                    &verification,
+                )
+                .await
+            }
+        };
+
+        let error = verify(verification("https://github.com/org/"))
+            .await
+            .unwrap_err();
+        assert!(
+            format!("{error:#}").contains("No attestations found"),
+            "{error:#}"
+        );
+
+        // Provenance signed by another key, or about another image, doesn't
+        // count.
+        let other_digest = format!("sha256:{}", "0".repeat(64));
+        attest_image(
+            &registry,
+            "org/plugin",
+            &image_digest,
+            &[
+                (
+                    &other_signer,
+                    provenance(&image_digest, "https://github.com/org/plugin"),
+                ),
+                (
+                    &signer,
+                    provenance(&other_digest, "https://github.com/org/plugin"),
+                ),
+            ],
+        );
+        let error = verify(verification("https://github.com/org/"))
+            .await
+            .unwrap_err();
+        assert!(
+            error.to_string().contains("No signed SLSA provenance"),
+            "{error:#}"
+        );
+
+        attest_image(
+            &registry,
+            "org/plugin",
+            &image_digest,
+            &[(
+                &signer,
+                provenance(&image_digest, "https://github.com/org/plugin"),
+            )],
+        );
+        verify(verification("https://github.com/org/"))
+            .await
+            .unwrap();
+        let error = verify(verification("https://github.com/other/"))
+            .await
+            .unwrap_err();
+        assert!(
+            error.to_string().contains("No SLSA provenance matches"),
+            "{error:#}"
+        );
+    }
+}
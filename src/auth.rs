@@ -0,0 +1,344 @@
+use crate::config::AuthConfig;
+use anyhow::{Context, Result};
+use clap::{Args, Subcommand};
+use keyring::Entry;
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+};
+use url::Url;
+
+pub const DEFAULT_KEYRING_SERVICE: &str = "hyper-mcp";
+
+#[derive(Args, Clone, Debug)]
+pub struct AuthArgs {
+    #[command(subcommand)]
+    pub command: AuthCommand,
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum AuthCommand {
+    /// Store credentials for a URL in the system keyring
+    Set {
+        /// URL prefix the credentials apply to
+        url: Url,
+
+        #[arg(long, help = "Username for basic auth, a token is stored when omitted")]
+        username: Option<String>,
+
+        #[arg(
+            long,
+            conflicts_with = "username",
+            help = "Store the token as a registry identity token, exchanged for an access token on pull"
+        )]
+        identity_token: bool,
+
+        #[arg(
+            long,
+            help = "Password or token, read from stdin when omitted",
+            env = "HYPER_MCP_AUTH_SECRET",
+            hide_env_values = true
+        )]
+        secret: Option<String>,
+
+        #[command(flatten)]
+        entry: EntryArgs,
+    },
+    /// Show the credentials stored for a URL
+    Get {
+        url: Url,
+
+        #[arg(long, help = "Print the stored JSON including the secret")]
+        show_secret: bool,
+
+        #[command(flatten)]
+        entry: EntryArgs,
+    },
+    /// Remove the credentials stored for a URL
+    Rm {
+        url: Url,
+
+        #[command(flatten)]
+        entry: EntryArgs,
+    },
+    /// List the credentials stored with `hyper-mcp auth set`
+    Ls,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct EntryArgs {
+    #[arg(long, help = "Keyring service [default: hyper-mcp]")]
+    service: Option<String>,
+
+    #[arg(long, help = "Keyring user [default: the URL]")]
+    user: Option<String>,
+}
+
+/// The keyring has no portable way to enumerate entries, so the entries
+/// created by `auth set` are tracked in a small index file next to the default
+/// config. It only holds URLs and keyring coordinates, never secrets.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+struct IndexEntry {
+    url: Url,
+    service: String,
+    user: String,
+}
+
+fn default_index_path() -> Result<PathBuf> {
+    dirs::config_dir()
+        .map(|path| path.join("hyper-mcp").join("keyring_auths.json"))
+        .context("Could not determine the user config directory")
+}
+
+fn read_index(path: &Path) -> Result<Vec<IndexEntry>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+fn write_index(path: &Path, index: &[IndexEntry]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(index)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn add_to_index(path: &Path, entry: IndexEntry) -> Result<()> {
+    let mut index = read_index(path)?;
+    index.retain(|e| e.url != entry.url);
+    index.push(entry);
+    index.sort_by(|a, b| a.url.as_str().cmp(b.url.as_str()));
+    write_index(path, &index)
+}
+
+fn remove_from_index(path: &Path, url: &Url) -> Result<()> {
+    let mut index = read_index(path)?;
+    index.retain(|e| &e.url != url);
+    write_index(path, &index)
+}
+
+/// Keyring coordinates for `url`: explicit flags win, then what `auth set`
+/// recorded, then the defaults.
+fn resolve_entry(index_path: &Path, url: &Url, entry: EntryArgs) -> Result<IndexEntry> {
+    let indexed = read_index(index_path)?.into_iter().find(|e| &e.url == url);
+    Ok(IndexEntry {
+        url: url.clone(),
+        service: entry
+            .service
+            .or_else(|| indexed.as_ref().map(|e| e.service.clone()))
+            .unwrap_or_else(|| DEFAULT_KEYRING_SERVICE.to_string()),
+        user: entry
+            .user
+            .or_else(|| indexed.map(|e| e.user))
+            .unwrap_or_else(|| url.to_string()),
+    })
+}
+
+/// The `auths` entry to paste into the config for a stored credential.
+fn config_snippet(entry: &IndexEntry) -> Result<String> {
+    let snippet = serde_json::json!({
+        "auths": {
+            entry.url.as_str(): {
+                "type": "keyring",
+                "service": entry.service,
+                "user": entry.user,
+            }
+        }
+    });
+    Ok(serde_yaml::to_string(&snippet)?)
+}
+
+fn read_secret(prompt: &str) -> Result<String> {
+    eprint!("{prompt}: ");
+    std::io::stderr().flush()?;
+    let mut secret = String::new();
+    std::io::stdin().lock().read_line(&mut secret)?;
+    let secret = secret.trim_end_matches(['\r', '\n']).to_string();
+    if secret.is_empty() {
+        return Err(anyhow::anyhow!("No secret given"));
+    }
+    Ok(secret)
+}
+
+fn keyring_entry(entry: &IndexEntry) -> Result<Entry> {
+    Entry::new(&entry.service, &entry.user).with_context(|| {
+        format!(
+            "Failed to open keyring entry for service {}, user {}",
+            entry.service, entry.user
+        )
+    })
+}
+
+pub fn run(args: AuthArgs) -> Result<()> {
+    let index_path = default_index_path()?;
+
+    match args.command {
+        AuthCommand::Set {
+            url,
+            username,
+            identity_token,
+            secret,
+            entry,
+        } => {
+            let auth_config = match username {
+                Some(username) => AuthConfig::Basic {
+                    username,
+                    password: secret.map_or_else(|| read_secret("Password"), Ok)?,
+                },
+                None if identity_token => AuthConfig::IdentityToken {
+                    identity_token: secret.map_or_else(|| read_secret("Identity token"), Ok)?,
+                },
+                None => AuthConfig::Token {
+                    token: secret.map_or_else(|| read_secret("Token"), Ok)?,
+                },
+            };
+            let entry = resolve_entry(&index_path, &url, entry)?;
+            keyring_entry(&entry)?
+                .set_password(&serde_json::to_string(&auth_config)?)
+                .context("Failed to store credentials in the keyring")?;
+            add_to_index(&index_path, entry.clone())?;
+
+            eprintln!("Stored credentials for {url}. Add this to your config:");
+            print!("{}", config_snippet(&entry)?);
+        }
+        AuthCommand::Get {
+            url,
+            show_secret,
+            entry,
+        } => {
+            let entry = resolve_entry(&index_path, &url, entry)?;
+            let secret = keyring_entry(&entry)?.get_password().with_context(|| {
+                format!(
+                    "No credentials found for {url} (service {}, user {})",
+                    entry.service, entry.user
+                )
+            })?;
+            let auth_config: AuthConfig = serde_json::from_str(&secret)
+                .context("Keyring entry does not hold a valid auth config")?;
+            if show_secret {
+                println!("{}", serde_json::to_string(&auth_config)?);
+            } else {
+                println!("{auth_config:?}");
+            }
+            print!("{}", config_snippet(&entry)?);
+        }
+        AuthCommand::Rm { url, entry } => {
+            let entry = resolve_entry(&index_path, &url, entry)?;
+            match keyring_entry(&entry)?.delete_credential() {
+                Ok(()) | Err(keyring::Error::NoEntry) => {}
+                Err(e) => return Err(e).context("Failed to remove credentials from the keyring"),
+            }
+            remove_from_index(&index_path, &url)?;
+            eprintln!("Removed credentials for {url}");
+        }
+        AuthCommand::Ls => {
+            for entry in read_index(&index_path)? {
+                println!(
+                    "{}\tservice={}\tuser={}",
+                    entry.url, entry.service, entry.user
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn index_entry(url: &str, user: &str) -> IndexEntry {
+        IndexEntry {
+            url: Url::parse(url).unwrap(),
+            service: DEFAULT_KEYRING_SERVICE.to_string(),
+            user: user.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_index_add_and_remove() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join("hyper-mcp").join("keyring_auths.json");
+        assert!(read_index(&index_path).unwrap().is_empty());
+
+        add_to_index(&index_path, index_entry("https://ghcr.io", "ghcr")).unwrap();
+        add_to_index(&index_path, index_entry("https://docker.io", "docker")).unwrap();
+        // Setting the same URL again replaces the entry.
+        add_to_index(&index_path, index_entry("https://ghcr.io", "ghcr2")).unwrap();
+
+        let index = read_index(&index_path).unwrap();
+        assert_eq!(
+            index,
+            vec![
+                index_entry("https://docker.io", "docker"),
+                index_entry("https://ghcr.io", "ghcr2"),
+            ]
+        );
+
+        remove_from_index(&index_path, &Url::parse("https://ghcr.io").unwrap()).unwrap();
+        assert_eq!(
+            read_index(&index_path).unwrap(),
+            vec![index_entry("https://docker.io", "docker")]
+        );
+    }
+
+    #[test]
+    fn test_resolve_entry_precedence() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join("keyring_auths.json");
+        let url = Url::parse("https://private.registry.io").unwrap();
+        let no_flags = EntryArgs {
+            service: None,
+            user: None,
+        };
+
+        let entry = resolve_entry(&index_path, &url, no_flags.clone()).unwrap();
+        assert_eq!(entry.service, DEFAULT_KEYRING_SERVICE);
+        assert_eq!(entry.user, "https://private.registry.io/");
+
+        add_to_index(
+            &index_path,
+            IndexEntry {
+                url: url.clone(),
+                service: "corp".to_string(),
+                user: "registry".to_string(),
+            },
+        )
+        .unwrap();
+        let entry = resolve_entry(&index_path, &url, no_flags).unwrap();
+        assert_eq!(entry.service, "corp");
+        assert_eq!(entry.user, "registry");
+
+        let entry = resolve_entry(
+            &index_path,
+            &url,
+            EntryArgs {
+                service: Some("other".to_string()),
+                user: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(entry.service, "other");
+        assert_eq!(entry.user, "registry");
+    }
+
+    #[test]
+    fn test_config_snippet_is_a_valid_auths_entry() {
+        let entry = index_entry("https://private.registry.io", "registry");
+        let snippet = config_snippet(&entry).unwrap();
+
+        let value: serde_json::Value = serde_yaml::from_str(&snippet).unwrap();
+        let auth = &value["auths"]["https://private.registry.io/"];
+        assert_eq!(auth["type"], "keyring");
+        assert_eq!(auth["service"], "hyper-mcp");
+        assert_eq!(auth["user"], "registry");
+    }
+}
@@ -1,8 +1,15 @@
 use anyhow::{Context, Result};
 use once_cell::sync::Lazy;
 use regex::Regex;
+use rmcp::schemars::{self, JsonSchema, Schema, SchemaGenerator, json_schema};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, convert::TryFrom, fmt, path::Path, str::FromStr};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 use url::Url;
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize)]
@@ -76,25 +83,96 @@ impl FromStr for PluginName {
     }
 }
 
+impl JsonSchema for PluginName {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "PluginName".into()
+    }
+
+    fn json_schema(_generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "string",
+            "pattern": PLUGIN_NAME_REGEX.as_str(),
+            "description": "Letters and digits, optionally separated by single underscores"
+        })
+    }
+}
+
 impl fmt::Display for PluginName {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.0)
     }
 }
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Serialize)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum AuthConfig {
-    Basic { username: String, password: String },
-    Token { token: String },
+    Basic {
+        username: String,
+        password: String,
+    },
+    Token {
+        token: String,
+    },
+    #[serde(rename = "identity_token")]
+    IdentityToken {
+        identity_token: String,
+    },
+}
+
+// Stand-in for secret values in `Debug` output, so logging a `Config` never
+// prints credentials.
+struct Redacted;
+
+impl fmt::Debug for Redacted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<redacted>")
+    }
+}
+
+impl fmt::Debug for AuthConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthConfig::Basic { username, .. } => f
+                .debug_struct("Basic")
+                .field("username", username)
+                .field("password", &Redacted)
+                .finish(),
+            AuthConfig::Token { .. } => f.debug_struct("Token").field("token", &Redacted).finish(),
+            AuthConfig::IdentityToken { .. } => f
+                .debug_struct("IdentityToken")
+                .field("identity_token", &Redacted)
+                .finish(),
+        }
+    }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+/// How to authenticate requests to URLs starting with this prefix.
+#[derive(Debug, Deserialize, JsonSchema, Serialize)]
 #[serde(tag = "type", rename_all = "lowercase")]
+#[schemars(crate = "rmcp::schemars", rename = "AuthConfig")]
 enum InternalAuthConfig {
+    /// HTTP basic auth.
     Basic { username: String, password: String },
+    /// A basic or token auth config stored as JSON in the system keyring.
     Keyring { service: String, user: String },
+    /// Bearer token auth.
     Token { token: String },
+    /// An OCI registry identity token, the OAuth2 refresh token `docker login`
+    /// stores for ACR, GCR and Harbor, exchanged for an access token.
+    #[serde(rename = "identity_token")]
+    IdentityToken { identity_token: String },
+}
+
+// The config file holds the internal form, keyring entries are only resolved
+// while deserializing.
+impl JsonSchema for AuthConfig {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        InternalAuthConfig::schema_name()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        InternalAuthConfig::json_schema(generator)
+    }
 }
 
 impl<'de> Deserialize<'de> for AuthConfig {
@@ -108,6 +186,9 @@ impl<'de> Deserialize<'de> for AuthConfig {
                 Ok(AuthConfig::Basic { username, password })
             }
             InternalAuthConfig::Token { token } => Ok(AuthConfig::Token { token }),
+            InternalAuthConfig::IdentityToken { identity_token } => {
+                Ok(AuthConfig::IdentityToken { identity_token })
+            }
             InternalAuthConfig::Keyring { service, user } => {
                 use keyring::Entry;
                 use serde::de;
@@ -122,50 +203,1051 @@ impl<'de> Deserialize<'de> for AuthConfig {
     }
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+/// hyper-mcp configuration file.
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema, Serialize)]
+#[schemars(crate = "rmcp::schemars")]
 pub struct Config {
+    /// Config files, or directories of them, to merge into this one. Relative
+    /// paths are resolved against the directory of this file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include: Option<Vec<PathBuf>>,
+    /// Credentials for HTTPS plugin downloads, keyed by URL prefix.
+    #[schemars(with = "Option<HashMap<String, AuthConfig>>")]
     pub auths: Option<HashMap<Url, AuthConfig>>,
+    /// Signature verification for `oci://` plugins, keyed by image prefix
+    /// such as `ghcr.io/myorg/`. A plugin's own `verification` takes precedence.
+    pub verification: Option<HashMap<String, VerificationConfig>>,
+    /// How OCI registries are reached, for pulls and signature lookups alike.
+    pub registries: Option<RegistriesConfig>,
+    #[serde(default)]
     pub plugins: HashMap<PluginName, PluginConfig>,
+    // The file each plugin was defined in.
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub sources: HashMap<PluginName, PathBuf>,
+}
+
+impl Config {
+    /// Merges `other`, loaded from `source`, into this config. Plugin names
+    /// must be unique across files, and `verification` and registry mirror
+    /// prefixes may only be repeated with the same settings; nothing is merged
+    /// otherwise. For `auths` the file merged last wins.
+    pub fn merge(&mut self, other: Config, source: &Path) -> Result<()> {
+        let plugin_source = |plugin_name: &PluginName| {
+            other
+                .sources
+                .get(plugin_name)
+                .cloned()
+                .unwrap_or_else(|| source.to_path_buf())
+        };
+        let mut plugin_names: Vec<_> = other.plugins.keys().collect();
+        plugin_names.sort_by_key(|plugin_name| plugin_name.as_str());
+        if let Some(plugin_name) = plugin_names
+            .into_iter()
+            .find(|plugin_name| self.plugins.contains_key(plugin_name))
+        {
+            return Err(DuplicatePluginError {
+                plugin_name: plugin_name.clone(),
+                file: plugin_source(plugin_name),
+                defined_in: self.source_of(plugin_name),
+            }
+            .into());
+        }
+        if let Some(prefix) =
+            conflicting_key(self.verification.as_ref(), other.verification.as_ref())
+        {
+            return Err(ConflictingEntryError {
+                keys: vec!["verification".to_string(), prefix.clone()],
+                file: source.to_path_buf(),
+            }
+            .into());
+        }
+        if let Some(prefix) = conflicting_key(
+            self.registries.as_ref().and_then(|r| r.mirrors.as_ref()),
+            other.registries.as_ref().and_then(|r| r.mirrors.as_ref()),
+        ) {
+            return Err(ConflictingEntryError {
+                keys: vec![
+                    "registries".to_string(),
+                    "mirrors".to_string(),
+                    prefix.clone(),
+                ],
+                file: source.to_path_buf(),
+            }
+            .into());
+        }
+        for (plugin_name, plugin_cfg) in &other.plugins {
+            self.plugins.insert(plugin_name.clone(), plugin_cfg.clone());
+            self.sources
+                .insert(plugin_name.clone(), plugin_source(plugin_name));
+        }
+
+        for (url, auth) in other.auths.into_iter().flatten() {
+            let auths = self.auths.get_or_insert_with(HashMap::new);
+            if auths.insert(url.clone(), auth).is_some() {
+                log::warn!(
+                    "auths entry {url} from {} overrides an earlier one",
+                    source.display()
+                );
+            }
+        }
+
+        if let Some(verification) = other.verification {
+            self.verification
+                .get_or_insert_with(HashMap::new)
+                .extend(verification);
+        }
+
+        if let Some(other) = other.registries {
+            let registries = self.registries.get_or_insert_with(Default::default);
+            registries
+                .insecure
+                .get_or_insert_with(Vec::new)
+                .extend(other.insecure.into_iter().flatten());
+            registries
+                .ca_certs
+                .get_or_insert_with(Vec::new)
+                .extend(other.ca_certs.into_iter().flatten());
+            if let Some(mirrors) = other.mirrors {
+                registries
+                    .mirrors
+                    .get_or_insert_with(HashMap::new)
+                    .extend(mirrors);
+            }
+        }
+        Ok(())
+    }
+
+    /// The verification settings for an `oci://` plugin: its own block, or
+    /// the one of the longest image prefix matching its URL.
+    pub fn verification_for<'a>(
+        &'a self,
+        plugin_cfg: &'a PluginConfig,
+    ) -> Option<&'a VerificationConfig> {
+        if plugin_cfg.verification.is_some() {
+            return plugin_cfg.verification.as_ref();
+        }
+        let image_reference = plugin_cfg.url.as_str().strip_prefix("oci://")?;
+        self.verification
+            .iter()
+            .flatten()
+            .filter(|(prefix, _)| is_image_prefix(prefix, image_reference))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, verification)| verification)
+    }
+
+    /// Where `image_reference` is pulled from: its mirror, if one matches.
+    pub fn pull_reference(&self, image_reference: &str) -> String {
+        self.registries.as_ref().map_or_else(
+            || image_reference.to_string(),
+            |r| r.mirror(image_reference),
+        )
+    }
+
+    /// The file `plugin_name` was defined in, for error messages.
+    pub fn source_of(&self, plugin_name: &PluginName) -> String {
+        self.sources.get(plugin_name).map_or_else(
+            || "the config".to_string(),
+            |path| path.display().to_string(),
+        )
+    }
+}
+
+/// A plugin defined in more than one config file.
+#[derive(Debug)]
+pub struct DuplicatePluginError {
+    pub plugin_name: PluginName,
+    pub file: PathBuf,
+    pub defined_in: String,
+}
+
+impl fmt::Display for DuplicatePluginError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Plugin {} in {} is already defined in {}",
+            self.plugin_name,
+            self.file.display(),
+            self.defined_in
+        )
+    }
+}
+
+impl std::error::Error for DuplicatePluginError {}
+
+/// An entry another config file already set differently, such as a
+/// `verification` prefix.
+#[derive(Debug)]
+pub struct ConflictingEntryError {
+    // Where the entry is, e.g. `["verification", "ghcr.io/"]`.
+    pub keys: Vec<String>,
+    pub file: PathBuf,
+}
+
+impl fmt::Display for ConflictingEntryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} in {} is already set differently in another config file",
+            self.keys.join("."),
+            self.file.display()
+        )
+    }
+}
+
+impl std::error::Error for ConflictingEntryError {}
+
+/// The first key of `other` that `existing` maps to a different value.
+fn conflicting_key<'a, V: PartialEq>(
+    existing: Option<&HashMap<String, V>>,
+    other: Option<&'a HashMap<String, V>>,
+) -> Option<&'a String> {
+    let existing = existing?;
+    let mut keys: Vec<_> = other?
+        .iter()
+        .filter(|(key, value)| {
+            existing
+                .get(*key)
+                .is_some_and(|existing| existing != *value)
+        })
+        .map(|(key, _)| key)
+        .collect();
+    keys.sort();
+    keys.into_iter().next()
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
+#[schemars(crate = "rmcp::schemars", transform = url_or_path)]
 pub struct PluginConfig {
+    /// `oci://`, `https://`, `http://`, `file://` or `s3://` URL of the plugin,
+    /// or a local path (relative to the config file, or starting with `~`).
     #[serde(rename = "url", alias = "path")]
+    #[schemars(with = "String")]
     pub url: Url,
     pub runtime_config: Option<RuntimeConfig>,
+    /// Signature verification for an `oci://` plugin.
+    pub verification: Option<VerificationConfig>,
+}
+
+/// How an OCI plugin's signature is verified. Unset fields fall back to the
+/// command line flags.
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+#[schemars(crate = "rmcp::schemars")]
+pub struct VerificationConfig {
+    /// Don't verify the signature, e.g. for unsigned internal plugins.
+    pub skip: Option<bool>,
+    /// Keyless signing identities to accept, a signature by any of them is enough.
+    pub identities: Option<Vec<SigningIdentity>>,
+    /// PEM public keys for signatures made with `cosign sign --key`, relative
+    /// to the config file or starting with `~`. A signature by any of them is
+    /// enough, and keyless signatures are not accepted.
+    pub public_keys: Option<Vec<PathBuf>>,
+    /// In-toto attestations the image must carry, signed like the image.
+    pub attestations: Option<AttestationPolicy>,
+}
+
+/// What the attestations attached with `cosign attest` must show.
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+#[schemars(crate = "rmcp::schemars")]
+pub struct AttestationPolicy {
+    /// Require SLSA provenance. Implied by `builder_id` and `source_repo_prefix`.
+    pub provenance: Option<bool>,
+    /// Builder id the SLSA provenance must name, e.g.
+    /// `https://github.com/slsa-framework/slsa-github-generator/.github/workflows/generator_container_slsa3.yml@refs/tags/v2.0.0`.
+    pub builder_id: Option<String>,
+    /// Prefix of the source repository the SLSA provenance must name, e.g.
+    /// `https://github.com/tuananh/`.
+    pub source_repo_prefix: Option<String>,
+    /// Require an SPDX or CycloneDX SBOM.
+    pub sbom: Option<bool>,
+}
+
+/// How OCI registries are reached.
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+#[schemars(crate = "rmcp::schemars")]
+pub struct RegistriesConfig {
+    /// Registries spoken to over plain HTTP, as `host` or `host:port`, e.g.
+    /// `localhost:5000`.
+    pub insecure: Option<Vec<String>>,
+    /// PEM files of extra CA certificates to trust for registries, relative to
+    /// the config file or starting with `~`.
+    pub ca_certs: Option<Vec<PathBuf>>,
+    /// Image prefixes to pull from elsewhere, e.g. `ghcr.io/tuananh/` to
+    /// `mirror.internal.example.com/tuananh/`. The longest matching prefix is
+    /// replaced.
+    pub mirrors: Option<HashMap<String, String>>,
+}
+
+impl RegistriesConfig {
+    /// `image_reference` with the longest matching mirror prefix replaced.
+    pub fn mirror(&self, image_reference: &str) -> String {
+        self.mirrors
+            .iter()
+            .flatten()
+            .filter(|(prefix, _)| is_image_prefix(prefix, image_reference))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or_else(
+                || image_reference.to_string(),
+                |(prefix, mirror)| format!("{mirror}{}", &image_reference[prefix.len()..]),
+            )
+    }
+}
+
+/// Whether `prefix` covers `image_reference` on a path segment boundary:
+/// `ghcr.io/org` covers `ghcr.io/org`, `ghcr.io/org/tool:1.0`,
+/// `ghcr.io/org:1.0` and `ghcr.io/org@sha256:...`, but not `ghcr.io/org-evil`.
+fn is_image_prefix(prefix: &str, image_reference: &str) -> bool {
+    image_reference.strip_prefix(prefix).is_some_and(|rest| {
+        rest.is_empty() || prefix.ends_with('/') || rest.starts_with(['/', ':', '@'])
+    })
 }
 
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+/// A keyless (Fulcio) signing identity. Both fields are regular expressions
+/// that must match the whole value.
+#[derive(Clone, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+#[schemars(crate = "rmcp::schemars")]
+pub struct SigningIdentity {
+    /// OIDC issuer of the signing certificate, e.g.
+    /// `https://token.actions.githubusercontent.com`. Any issuer when not set.
+    pub issuer: Option<String>,
+    /// Email or URI subject of the signing certificate.
+    pub subject: String,
+}
+
+// `path` is accepted as an alias of `url`, exactly one of them must be set.
+fn url_or_path(schema: &mut Schema) {
+    let Some(schema) = schema.as_object_mut() else {
+        return;
+    };
+    if let Some(properties) = schema.get_mut("properties").and_then(|p| p.as_object_mut()) {
+        if let Some(url) = properties.get("url").cloned() {
+            properties.insert("path".to_string(), url);
+        }
+    }
+    schema.remove("required");
+    schema.insert(
+        "oneOf".to_string(),
+        serde_json::json!([{ "required": ["url"] }, { "required": ["path"] }]),
+    );
+}
+
+/// A host directory mounted into the plugin.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(try_from = "InternalAllowedPath")]
+pub struct AllowedPath {
+    pub host: String,
+    // Where the plugin sees the directory, `host` when not set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub guest: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub read_only: bool,
+}
+
+impl AllowedPath {
+    pub fn guest(&self) -> &str {
+        self.guest.as_deref().unwrap_or(&self.host)
+    }
+}
+
+impl FromStr for AllowedPath {
+    type Err = String;
+
+    /// Parses `host`, `host:guest`, `host:ro` or `host:guest:ro` (`rw` is
+    /// accepted in place of `ro` and is the default). A Windows drive letter
+    /// such as `C:\` or `C:/` starting either path is not a separator.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid allowed path {s}, expected host[:guest][:ro|rw]");
+        let mut parts = split_allowed_path(s);
+        let mode = match parts[..] {
+            [_, .., mode @ ("ro" | "rw")] => Some(mode),
+            _ => None,
+        };
+        if mode.is_some() {
+            parts.pop();
+        }
+        let read_only = mode == Some("ro");
+        let (host, guest) = match parts[..] {
+            [host] => (host, None),
+            [host, guest] => (host, Some(guest.to_string())),
+            _ => return Err(invalid()),
+        };
+        if host.is_empty() || guest.as_deref() == Some("") {
+            return Err(invalid());
+        }
+        Ok(AllowedPath {
+            host: host.to_string(),
+            guest,
+            read_only,
+        })
+    }
+}
+
+/// Splits an allowed path on `:`, keeping a leading drive letter (`C:\`,
+/// `C:/`) of each part with the path it starts.
+fn split_allowed_path(s: &str) -> Vec<&str> {
+    let is_drive = |rest: &str| {
+        let bytes = rest.as_bytes();
+        bytes.len() > 2
+            && bytes[0].is_ascii_alphabetic()
+            && bytes[1] == b':'
+            && matches!(bytes[2], b'\\' | b'/')
+    };
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut search = 0;
+    while let Some(offset) = s[search..].find(':') {
+        let colon = search + offset;
+        if colon == start + 1 && is_drive(&s[start..]) {
+            search = colon + 1;
+            continue;
+        }
+        parts.push(&s[start..colon]);
+        start = colon + 1;
+        search = start;
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+/// A host directory mounted into the plugin, either `host[:guest][:ro|rw]`
+/// or an object.
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+#[schemars(crate = "rmcp::schemars", rename = "AllowedPath")]
+enum InternalAllowedPath {
+    Short(String),
+    Full {
+        /// Directory on the host, relative to the config file or starting with `~`.
+        host: String,
+        /// Where the plugin sees the directory, the host path when not set.
+        guest: Option<String>,
+        /// Mount the directory read-only.
+        #[serde(default)]
+        read_only: bool,
+    },
+}
+
+impl TryFrom<InternalAllowedPath> for AllowedPath {
+    type Error = String;
+
+    fn try_from(value: InternalAllowedPath) -> Result<Self, Self::Error> {
+        match value {
+            InternalAllowedPath::Short(s) => s.parse(),
+            InternalAllowedPath::Full {
+                host,
+                guest,
+                read_only,
+            } => Ok(AllowedPath {
+                host,
+                guest,
+                read_only,
+            }),
+        }
+    }
+}
+
+impl JsonSchema for AllowedPath {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        InternalAllowedPath::schema_name()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        InternalAllowedPath::json_schema(generator)
+    }
+}
+
+#[derive(Clone, Default, Deserialize, JsonSchema, Serialize)]
+#[schemars(crate = "rmcp::schemars")]
 pub struct RuntimeConfig {
-    // List of tool names to skip loading at runtime.
+    /// List of tool names to skip loading at runtime.
     pub skip_tools: Option<Vec<String>>,
+    /// Hosts the plugin may make HTTP requests to (`*` for any).
     pub allowed_hosts: Option<Vec<String>>,
-    pub allowed_paths: Option<Vec<String>>,
-    pub env_vars: Option<HashMap<String, String>>,
+    /// Host paths the plugin may access.
+    pub allowed_paths: Option<Vec<AllowedPath>>,
+    /// Config values passed to the plugin. Literal values or typed sources,
+    /// resolved when the plugin is loaded.
+    #[serde(default, deserialize_with = "deserialize_env_vars")]
+    pub env_vars: Option<HashMap<String, EnvVarValue>>,
+    /// Environment variables of the hyper-mcp process to pass to the plugin
+    /// as config values. `*` matches any characters, e.g. `QDRANT_*`.
+    pub env_passthrough: Option<Vec<String>>,
+    /// Maximum plugin memory, e.g. `512Mi`.
     pub memory_limit: Option<String>,
+    /// Allow the plugin to request LLM sampling from the client during a tool call.
+    pub allow_sampling: Option<bool>,
+    /// Maximum number of sampling requests the plugin may make per tool call.
+    pub max_sampling_requests: Option<u32>,
+    /// Namespaced tools of other plugins (e.g. `fetch-fetch` or `arxiv-*`) this
+    /// plugin may call through the `call_tool` host function.
+    pub allowed_tools: Option<Vec<String>>,
+    /// Mount the client's workspace roots instead of `allowed_paths`, which
+    /// become the ceiling roots must lie within.
+    pub use_client_roots: Option<bool>,
+}
+
+impl RuntimeConfig {
+    /// The `env_vars` values, with their sources read.
+    pub fn resolve_env_vars(&self) -> Result<HashMap<String, String>> {
+        self.env_vars
+            .iter()
+            .flatten()
+            .map(|(key, value)| {
+                let value = value
+                    .resolve()
+                    .with_context(|| format!("Failed to resolve env_vars.{key}"))?;
+                Ok((key.clone(), value))
+            })
+            .collect()
+    }
+}
+
+impl fmt::Debug for RuntimeConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // env_vars commonly carry tokens, only show which keys are set.
+        let env_vars = self.env_vars.as_ref().map(|env_vars| {
+            env_vars
+                .keys()
+                .map(|key| (key, Redacted))
+                .collect::<HashMap<_, _>>()
+        });
+        f.debug_struct("RuntimeConfig")
+            .field("skip_tools", &self.skip_tools)
+            .field("allowed_hosts", &self.allowed_hosts)
+            .field("allowed_paths", &self.allowed_paths)
+            .field("env_vars", &env_vars)
+            .field("env_passthrough", &self.env_passthrough)
+            .field("memory_limit", &self.memory_limit)
+            .field("allow_sampling", &self.allow_sampling)
+            .field("max_sampling_requests", &self.max_sampling_requests)
+            .field("allowed_tools", &self.allowed_tools)
+            .field("use_client_roots", &self.use_client_roots)
+            .finish()
+    }
+}
+
+// Matches `${provider:argument}`, or `$${...}` which escapes to a literal `${...}`.
+static INTERPOLATION_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\$(\$?)\{([^}]*)\}").expect("Failed to compile interpolation regex"));
+
+/// Where a secret is read from, either in a `${provider:argument}` reference
+/// or as a typed `env_vars` entry.
+#[derive(Clone, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", deny_unknown_fields)]
+#[schemars(crate = "rmcp::schemars")]
+pub enum SecretSource {
+    /// Name of an environment variable of the hyper-mcp process.
+    Env(String),
+    /// Path of a file holding the secret.
+    File(PathBuf),
+    /// Password stored in the system keyring.
+    Keyring { service: String, user: String },
+}
+
+/// An `env_vars` value. Sources are kept as written, so the config never
+/// holds, or serializes, the secrets they point at.
+#[derive(Clone, Debug, JsonSchema, PartialEq, Serialize)]
+#[serde(untagged)]
+#[schemars(crate = "rmcp::schemars")]
+pub enum EnvVarValue {
+    Literal(String),
+    Source(SecretSource),
+}
+
+impl EnvVarValue {
+    pub fn resolve(&self) -> Result<String> {
+        match self {
+            EnvVarValue::Literal(literal) => Ok(literal.clone()),
+            EnvVarValue::Source(source) => source.resolve(),
+        }
+    }
+}
+
+impl From<&str> for EnvVarValue {
+    fn from(literal: &str) -> Self {
+        EnvVarValue::Literal(literal.to_string())
+    }
+}
+
+impl SecretSource {
+    fn resolve(&self) -> Result<String> {
+        match self {
+            SecretSource::Env(name) => std::env::var(name)
+                .with_context(|| format!("Environment variable {name} is not set")),
+            SecretSource::File(path) => std::fs::read_to_string(path)
+                .map(|secret| secret.trim_end_matches(['\r', '\n']).to_string())
+                .with_context(|| format!("Failed to read secret file {}", path.display())),
+            SecretSource::Keyring { service, user } => keyring::Entry::new(service, user)
+                .and_then(|entry| entry.get_password())
+                .with_context(|| {
+                    format!("Failed to read keyring entry for service {service}, user {user}")
+                }),
+        }
+    }
+}
+
+/// Resolves a single `${provider:argument}` reference. Relative `file` paths
+/// are resolved against `base_dir`.
+fn resolve_reference(reference: &str, base_dir: &Path) -> Result<String> {
+    let Some((provider, argument)) = reference.split_once(':') else {
+        return Err(anyhow::anyhow!(
+            "Invalid reference ${{{reference}}}, expected ${{env:NAME}}, ${{file:PATH}} or ${{keyring:SERVICE/USER}} (use $${{...}} for a literal)"
+        ));
+    };
+    let source = match provider {
+        "env" => SecretSource::Env(argument.to_string()),
+        "file" => SecretSource::File(resolve_path(argument, base_dir)),
+        "keyring" => {
+            let (service, user) = argument.rsplit_once('/').with_context(|| {
+                format!("Invalid keyring reference {argument}, expected SERVICE/USER")
+            })?;
+            SecretSource::Keyring {
+                service: service.to_string(),
+                user: user.to_string(),
+            }
+        }
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Unknown provider {provider} in ${{{reference}}}, expected env, file or keyring (use $${{...}} for a literal)"
+            ));
+        }
+    };
+    source.resolve()
 }
 
+/// Reads `env_vars`, where each value is either a literal string or a typed
+/// source (`{env: NAME}`, `{file: PATH}` or `{keyring: {service, user}}`).
+fn deserialize_env_vars<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Option<HashMap<String, EnvVarValue>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::de;
+
+    let Some(env_vars) = Option::<HashMap<String, serde_json::Value>>::deserialize(deserializer)?
+    else {
+        return Ok(None);
+    };
+    env_vars
+        .into_iter()
+        .map(|(key, value)| {
+            let value = match value {
+                serde_json::Value::String(literal) => EnvVarValue::Literal(literal),
+                source => EnvVarValue::Source(serde_json::from_value(source).map_err(|e| {
+                    de::Error::custom(format!(
+                        "env_vars.{key}: expected a string, {{env: NAME}}, {{file: PATH}} or {{keyring: {{service, user}}}}: {e}"
+                    ))
+                })?),
+            };
+            Ok((key, value))
+        })
+        .collect::<std::result::Result<_, _>>()
+        .map(Some)
+}
+
+/// Resolves every `${provider:argument}` reference in a single string value.
+fn interpolate_str(value: &str, base_dir: &Path) -> Result<String> {
+    let mut result = String::with_capacity(value.len());
+    let mut last = 0;
+    for captures in INTERPOLATION_REGEX.captures_iter(value) {
+        let whole = captures.get(0).unwrap();
+        result.push_str(&value[last..whole.start()]);
+        if captures[1].is_empty() {
+            result.push_str(&resolve_reference(&captures[2], base_dir)?);
+        } else {
+            result.push_str(&whole.as_str()[1..]);
+        }
+        last = whole.end();
+    }
+    result.push_str(&value[last..]);
+    Ok(result)
+}
+
+/// A config value with a `${...}` reference that couldn't be resolved.
+#[derive(Debug)]
+pub struct InterpolationError {
+    /// The map keys leading to the value.
+    pub keys: Vec<String>,
+    /// The value as written.
+    pub value: String,
+    /// The dotted path of the value, e.g. `plugins.gitlab.runtime_config.env_vars.TOKEN`.
+    pub location: String,
+    pub error: anyhow::Error,
+}
+
+impl fmt::Display for InterpolationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to resolve {}", self.location)
+    }
+}
+
+impl std::error::Error for InterpolationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.error.as_ref())
+    }
+}
+
+/// Replaces `${env:NAME}`, `${file:PATH}` and `${keyring:SERVICE/USER}` in
+/// every string value (map keys are left alone). `keys` and `location` lead
+/// to `value` in the config, and `base_dir` is the directory relative `file`
+/// paths are resolved against.
+fn interpolate(
+    value: &mut serde_json::Value,
+    keys: &mut Vec<String>,
+    location: &str,
+    base_dir: &Path,
+) -> std::result::Result<(), InterpolationError> {
+    match value {
+        serde_json::Value::String(s) => {
+            *s = interpolate_str(s, base_dir).map_err(|error| InterpolationError {
+                keys: keys.clone(),
+                value: s.clone(),
+                location: location.to_string(),
+                error,
+            })?;
+        }
+        serde_json::Value::Array(values) => {
+            for (i, value) in values.iter_mut().enumerate() {
+                interpolate(value, keys, &format!("{location}[{i}]"), base_dir)?;
+            }
+        }
+        serde_json::Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                let location = if location.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{location}.{key}")
+                };
+                keys.push(key.clone());
+                interpolate(value, keys, &location, base_dir)?;
+                keys.pop();
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// JSON Schema of the config file, as printed by `hyper-mcp config schema`.
+pub fn config_schema() -> Schema {
+    schemars::schema_for!(Config)
+}
+
+/// `config.json` in the hyper-mcp directory of the user's config directory.
+pub fn default_config_path() -> PathBuf {
+    dirs::config_dir()
+        .map(|mut path| {
+            path.push("hyper-mcp");
+            path.push("config.json");
+            path
+        })
+        .unwrap()
+}
+
+/// Config files to merge for an `include` entry: the file itself, or the
+/// `*.yaml`, `*.yml`, `*.json` and `*.toml` files of a directory in
+/// alphabetical order. Relative entries are resolved against `base_dir`.
+pub fn include_files(base_dir: &Path, include: &Path) -> Result<Vec<PathBuf>> {
+    let path = base_dir.join(include);
+    if !path.is_dir() {
+        return Ok(vec![path]);
+    }
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(&path)
+        .with_context(|| format!("Failed to read config directory {}", path.display()))?
+    {
+        let file = entry?.path();
+        let ext = file.extension().and_then(|e| e.to_str()).unwrap_or("");
+        if file.is_file() && matches!(ext, "json" | "yaml" | "yml" | "toml") {
+            files.push(file);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Loads the config at `path` and, depth first, every file it includes.
 pub async fn load_config(path: &Path) -> Result<Config> {
+    load_config_files(&[path.to_path_buf()])
+}
+
+/// Reads the config files at `paths`, each followed by the files it
+/// includes, and merges them in that order.
+pub fn load_config_files(paths: &[PathBuf]) -> Result<Config> {
+    let mut loaded = Vec::new();
+    let mut files = Vec::new();
+    let mut error = None;
+    for path in paths {
+        read_config_tree(path, None, &mut loaded, &mut files, &mut |e| {
+            error.get_or_insert(e);
+        });
+        if let Some(e) = error.take() {
+            return Err(e.into());
+        }
+    }
+
+    let mut config = Config::default();
+    for file in files {
+        config.merge(file.config, &file.path)?;
+    }
+    Ok(config)
+}
+
+/// A config file, read and resolved but not merged with the others yet.
+pub struct ConfigFile {
+    pub path: PathBuf,
+    pub content: String,
+    pub config: Config,
+}
+
+/// A config file that couldn't be read.
+#[derive(Debug)]
+pub struct ConfigFileError {
+    pub path: PathBuf,
+    pub included_from: Option<PathBuf>,
+    /// The text of the file, when it could be read.
+    pub content: Option<String>,
+    pub error: anyhow::Error,
+}
+
+impl fmt::Display for ConfigFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.included_from {
+            Some(included_from) => write!(
+                f,
+                "In {} included from {}: {:#}",
+                self.path.display(),
+                included_from.display(),
+                self.error
+            ),
+            None => write!(f, "{:#}", self.error),
+        }
+    }
+}
+
+impl std::error::Error for ConfigFileError {}
+
+/// Reads the config file at `path` and, depth first, every file it includes
+/// into `files`, in the order they are merged. A file that can't be read is
+/// passed to `on_error` and skipped along with its includes. `loaded` holds
+/// the files read so far, so include cycles and files included twice are
+/// caught.
+pub fn read_config_tree(
+    path: &Path,
+    included_from: Option<&Path>,
+    loaded: &mut Vec<PathBuf>,
+    files: &mut Vec<ConfigFile>,
+    on_error: &mut dyn FnMut(ConfigFileError),
+) {
+    let error = |content: Option<&str>, error| ConfigFileError {
+        path: path.to_path_buf(),
+        included_from: included_from.map(Path::to_path_buf),
+        content: content.map(str::to_string),
+        error,
+    };
+
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    if loaded.contains(&canonical) {
+        on_error(error(
+            None,
+            anyhow::anyhow!("Config file {} is included more than once", path.display()),
+        ));
+        return;
+    }
+    loaded.push(canonical);
+
     if !path.exists() {
-        return Err(anyhow::anyhow!(
-            "Config file not found at: {}. Please create a config file first.",
-            path.display()
+        on_error(error(
+            None,
+            anyhow::anyhow!(
+                "Config file not found at: {}. Please create a config file first.",
+                path.display()
+            ),
         ));
+        return;
     }
-    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let content = match std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file at {}", path.display()))
+    {
+        Ok(content) => content,
+        Err(e) => {
+            on_error(error(None, e));
+            return;
+        }
+    };
+    let config = match read_config_file(path, &content) {
+        Ok(config) => config,
+        Err(e) => {
+            on_error(error(Some(&content), e));
+            return;
+        }
+    };
 
-    let content = tokio::fs::read_to_string(path)
-        .await
-        .with_context(|| format!("Failed to read config file at {}", path.display()))?;
+    let includes = config.include.clone().unwrap_or_default();
+    files.push(ConfigFile {
+        path: path.to_path_buf(),
+        content,
+        config,
+    });
+    let content = files.len() - 1;
+
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+    for include in includes {
+        match include_files(base_dir, &include) {
+            Ok(include_files) => {
+                for file in include_files {
+                    read_config_tree(&file, Some(path), loaded, files, on_error);
+                }
+            }
+            Err(e) => on_error(error(Some(&files[content].content), e)),
+        }
+    }
+}
 
-    let config = match ext {
-        "json" => serde_json::from_str(&content)?,
-        "yaml" | "yml" => serde_yaml::from_str(&content)?,
-        "toml" => toml::from_str(&content)?,
+/// Parses, interpolates and deserializes `content`, the text of the config
+/// file at `path`.
+fn read_config_file(path: &Path, content: &str) -> Result<Config> {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let mut value: serde_json::Value = match ext {
+        "json" => serde_json::from_str(content)?,
+        "yaml" | "yml" => serde_yaml::from_str(content)?,
+        "toml" => toml::from_str(content)?,
         _ => return Err(anyhow::anyhow!("Unsupported config format: {}", ext)),
     };
+    let base_dir = config_dir(path)?;
+    interpolate(&mut value, &mut Vec::new(), "", &base_dir)?;
+    resolve_local_paths(&mut value, &base_dir)?;
 
-    Ok(config)
+    Ok(serde_path_to_error::deserialize(value)?)
+}
+
+/// Absolute directory of the config file at `path`, which relative paths in
+/// it are resolved against.
+fn config_dir(path: &Path) -> Result<PathBuf> {
+    let path = std::path::absolute(path)
+        .with_context(|| format!("Failed to resolve {}", path.display()))?;
+    Ok(path.parent().map(Path::to_path_buf).unwrap_or_default())
+}
+
+/// Expands a leading `~` and resolves a relative path against `base_dir`,
+/// dropping `.` and `..` components.
+fn resolve_path(path: &str, base_dir: &Path) -> PathBuf {
+    let expanded = match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => dirs::home_dir()
+            .map(|home| home.join(rest.trim_start_matches('/')))
+            .unwrap_or_else(|| PathBuf::from(path)),
+        _ => PathBuf::from(path),
+    };
+
+    let mut resolved = PathBuf::new();
+    for component in base_dir.join(expanded).components() {
+        match component {
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => {
+                resolved.pop();
+            }
+            component => resolved.push(component),
+        }
+    }
+    resolved
+}
+
+/// Whether a plugin `url` is a bare, relative or `~` path rather than a URL.
+fn is_local_path(url: &str) -> bool {
+    Path::new(url).is_absolute()
+        || matches!(
+            Url::parse(url),
+            Err(url::ParseError::RelativeUrlWithoutBase)
+        )
+}
+
+/// Turns local plugin paths into `file://` URLs and makes the host side of
+/// `allowed_paths` and `file` sources of `env_vars` absolute, all relative to
+/// the config file's directory.
+fn resolve_local_paths(value: &mut serde_json::Value, base_dir: &Path) -> Result<()> {
+    let verifications = value
+        .get_mut("verification")
+        .and_then(|v| v.as_object_mut());
+    for verification in verifications.into_iter().flat_map(|v| v.values_mut()) {
+        resolve_public_keys(verification, base_dir);
+    }
+    let ca_certs = value
+        .pointer_mut("/registries/ca_certs")
+        .and_then(|certs| certs.as_array_mut());
+    for ca_cert in ca_certs.into_iter().flatten() {
+        if let serde_json::Value::String(ca_cert) = ca_cert {
+            *ca_cert = resolve_path(ca_cert, base_dir)
+                .to_string_lossy()
+                .into_owned();
+        }
+    }
+
+    let Some(plugins) = value.get_mut("plugins").and_then(|p| p.as_object_mut()) else {
+        return Ok(());
+    };
+    for plugin in plugins.values_mut() {
+        if let Some(verification) = plugin.get_mut("verification") {
+            resolve_public_keys(verification, base_dir);
+        }
+
+        for key in ["url", "path"] {
+            if let Some(serde_json::Value::String(url)) = plugin.get_mut(key) {
+                if is_local_path(url) {
+                    let path = resolve_path(url, base_dir);
+                    *url = Url::from_file_path(&path)
+                        .map_err(|_| {
+                            anyhow::anyhow!("Can't use {} as a plugin path", path.display())
+                        })?
+                        .to_string();
+                }
+            }
+        }
+
+        let allowed_paths = plugin
+            .pointer_mut("/runtime_config/allowed_paths")
+            .and_then(|paths| paths.as_array_mut());
+        for value in allowed_paths.into_iter().flatten() {
+            // Malformed entries are left for deserialization to report.
+            let Ok(mut allowed_path) = serde_json::from_value::<AllowedPath>(value.clone()) else {
+                continue;
+            };
+            let host = resolve_path(&allowed_path.host, base_dir);
+            // Without an explicit guest path the plugin sees the resolved path.
+            allowed_path.host = host.to_string_lossy().into_owned();
+            *value = serde_json::to_value(allowed_path)?;
+        }
+
+        let env_vars = plugin
+            .pointer_mut("/runtime_config/env_vars")
+            .and_then(|env_vars| env_vars.as_object_mut());
+        for value in env_vars
+            .into_iter()
+            .flat_map(|env_vars| env_vars.values_mut())
+        {
+            if let Some(serde_json::Value::String(file)) = value.get_mut("file") {
+                *file = resolve_path(file, base_dir).to_string_lossy().into_owned();
+            }
+        }
+    }
+    Ok(())
+}
+
+fn resolve_public_keys(verification: &mut serde_json::Value, base_dir: &Path) {
+    let public_keys = verification
+        .get_mut("public_keys")
+        .and_then(|keys| keys.as_array_mut());
+    for public_key in public_keys.into_iter().flatten() {
+        if let serde_json::Value::String(public_key) = public_key {
+            *public_key = resolve_path(public_key, base_dir)
+                .to_string_lossy()
+                .into_owned();
+        }
+    }
 }
 
 #[cfg(test)]
@@ -539,10 +1621,13 @@ mod tests {
         // Verify env vars
         let test_plugin = &config.plugins[&PluginName("test_plugin".to_string())];
         let runtime_config = test_plugin.runtime_config.as_ref().unwrap();
-        assert_eq!(runtime_config.env_vars.as_ref().unwrap()["DEBUG"], "true");
+        assert_eq!(
+            runtime_config.env_vars.as_ref().unwrap()["DEBUG"],
+            "true".into()
+        );
         assert_eq!(
             runtime_config.env_vars.as_ref().unwrap()["LOG_LEVEL"],
-            "info"
+            "info".into()
         );
     }
 
@@ -575,11 +1660,255 @@ mod tests {
         let error = config_result.unwrap_err();
         assert!(
             error.to_string().contains("not a valid url")
-                || error.to_string().contains("invalid URL"),
+                || error.to_string().contains("invalid URL")
+                || error
+                    .to_string()
+                    .contains("invalid international domain name"),
             "Error should mention the invalid URL"
         );
     }
 
+    #[test]
+    fn test_load_config_includes() {
+        let rt = Runtime::new().unwrap();
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        std::fs::create_dir(dir.join("conf.d")).unwrap();
+        std::fs::write(
+            dir.join("conf.d/b_team.json"),
+            r#"{"plugins": {"qdrant": {"url": "oci://ghcr.io/tuananh/qdrant-plugin:latest"}}, "auths": {"https://example.com": {"type": "token", "token": "from-b"}}}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("conf.d/a_team.toml"),
+            "[plugins.fetch]\nurl = \"oci://ghcr.io/tuananh/fetch-plugin:latest\"\n\n[auths.\"https://example.com\"]\ntype = \"token\"\ntoken = \"from-a\"\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("conf.d/notes.txt"), "not a config").unwrap();
+        std::fs::write(
+            dir.join("config.yaml"),
+            "include:\n  - conf.d\nplugins:\n  time:\n    url: \"oci://ghcr.io/tuananh/time-plugin:latest\"\n",
+        )
+        .unwrap();
+
+        let config = rt.block_on(load_config(&dir.join("config.yaml"))).unwrap();
+        assert_eq!(config.plugins.len(), 3);
+        let source = |name: &str| config.sources[&PluginName::try_from(name).unwrap()].clone();
+        assert_eq!(source("time"), dir.join("config.yaml"));
+        assert_eq!(source("fetch"), dir.join("conf.d/a_team.toml"));
+        assert_eq!(source("qdrant"), dir.join("conf.d/b_team.json"));
+
+        // Fragments are merged in alphabetical order, the last auths entry wins.
+        let auths = config.auths.unwrap();
+        match &auths[&Url::parse("https://example.com").unwrap()] {
+            AuthConfig::Token { token } => assert_eq!(token, "from-b"),
+            _ => panic!("Expected Token auth config"),
+        }
+    }
+
+    #[test]
+    fn test_load_config_include_errors() {
+        let rt = Runtime::new().unwrap();
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path();
+
+        std::fs::write(
+            dir.join("config.yaml"),
+            "include:\n  - other.yaml\nplugins:\n  time:\n    url: \"oci://ghcr.io/tuananh/time-plugin:latest\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("other.yaml"),
+            "plugins:\n  time:\n    url: \"oci://ghcr.io/tuananh/time-plugin:v2\"\n",
+        )
+        .unwrap();
+        let error = rt
+            .block_on(load_config(&dir.join("config.yaml")))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("Plugin time in")
+                && error.contains("other.yaml")
+                && error.contains("is already defined in")
+                && error.contains("config.yaml"),
+            "Unexpected error: {error}"
+        );
+
+        // An include can't change how the including file's plugins are verified.
+        std::fs::write(
+            dir.join("config.yaml"),
+            "include:\n  - other.yaml\nverification:\n  \"ghcr.io/\":\n    identities:\n      - subject: \"^ci@example.com$\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("other.yaml"),
+            "verification:\n  \"ghcr.io/\":\n    skip: true\n",
+        )
+        .unwrap();
+        let error = rt
+            .block_on(load_config(&dir.join("config.yaml")))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("verification.ghcr.io/ in")
+                && error.contains("other.yaml")
+                && error.contains("is already set differently"),
+            "Unexpected error: {error}"
+        );
+
+        // Nor send its pulls to another registry.
+        std::fs::write(
+            dir.join("config.yaml"),
+            "include:\n  - other.yaml\nregistries:\n  mirrors:\n    \"docker.io/\": \"mirror.example.com/\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("other.yaml"),
+            "registries:\n  mirrors:\n    \"docker.io/\": \"evil.example.com/\"\n",
+        )
+        .unwrap();
+        let error = rt
+            .block_on(load_config(&dir.join("config.yaml")))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("registries.mirrors.docker.io/ in")
+                && error.contains("is already set differently"),
+            "Unexpected error: {error}"
+        );
+
+        // Repeating the same settings is fine.
+        std::fs::write(
+            dir.join("other.yaml"),
+            "registries:\n  mirrors:\n    \"docker.io/\": \"mirror.example.com/\"\n",
+        )
+        .unwrap();
+        assert!(rt.block_on(load_config(&dir.join("config.yaml"))).is_ok());
+
+        std::fs::write(dir.join("other.yaml"), "include:\n  - config.yaml\n").unwrap();
+        let error = rt
+            .block_on(load_config(&dir.join("config.yaml")))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("is included more than once"),
+            "Unexpected error: {error}"
+        );
+    }
+
+    #[test]
+    fn test_load_config_files() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        std::fs::write(
+            dir.join("time.yaml"),
+            "plugins:\n  time:\n    url: \"oci://ghcr.io/tuananh/time-plugin:latest\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("qdrant.yaml"),
+            "plugins:\n  qdrant:\n    url: \"oci://ghcr.io/tuananh/qdrant-plugin:latest\"\n",
+        )
+        .unwrap();
+
+        let fragments = include_files(Path::new(""), dir).unwrap();
+        let config = load_config_files(&fragments).unwrap();
+        assert_eq!(config.plugins.len(), 2);
+
+        let mut paths = vec![PathBuf::from("tests/fixtures/valid_config.yaml")];
+        paths.extend(fragments);
+        let config = load_config_files(&paths).unwrap();
+        assert!(config.plugins.len() > 2);
+        assert_eq!(
+            config.sources[&PluginName::try_from("time").unwrap()],
+            dir.join("time.yaml")
+        );
+    }
+
+    #[test]
+    fn test_load_config_resolves_local_paths() {
+        let rt = Runtime::new().unwrap();
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path().canonicalize().unwrap();
+        std::fs::create_dir(dir.join("team")).unwrap();
+        std::fs::write(
+            dir.join("team/config.yaml"),
+            r#"
+verification:
+  "ghcr.io/":
+    public_keys:
+      - "keys/ghcr.pub"
+plugins:
+  relative:
+    url: "./plugins/time.wasm"
+    runtime_config:
+      allowed_paths:
+        - "data"
+        - "../shared"
+        - "/tmp"
+        - "~/notes"
+  bare:
+    path: "qr code.wasm"
+  home:
+    url: "~/plugins/fetch.wasm"
+  absolute:
+    url: "/opt/plugins/memory.wasm"
+  remote:
+    url: "oci://ghcr.io/tuananh/time-plugin:latest"
+    verification:
+      public_keys:
+        - "~/cosign.pub"
+"#,
+        )
+        .unwrap();
+
+        let config = rt
+            .block_on(load_config(&dir.join("team/config.yaml")))
+            .unwrap();
+        let plugin = |name: &str| config.plugins[&PluginName::try_from(name).unwrap()].clone();
+        let file_path = |name: &str| plugin(name).url.to_file_path().unwrap();
+        let home = dirs::home_dir().unwrap();
+
+        assert_eq!(file_path("relative"), dir.join("team/plugins/time.wasm"));
+        assert_eq!(file_path("bare"), dir.join("team/qr code.wasm"));
+        assert_eq!(file_path("home"), home.join("plugins/fetch.wasm"));
+        assert_eq!(
+            file_path("absolute"),
+            PathBuf::from("/opt/plugins/memory.wasm")
+        );
+        assert_eq!(
+            plugin("remote").url.as_str(),
+            "oci://ghcr.io/tuananh/time-plugin:latest"
+        );
+
+        let allowed_paths: Vec<PathBuf> = plugin("relative")
+            .runtime_config
+            .unwrap()
+            .allowed_paths
+            .unwrap()
+            .into_iter()
+            .map(|allowed_path| PathBuf::from(allowed_path.host))
+            .collect();
+        assert_eq!(
+            allowed_paths,
+            vec![
+                dir.join("team/data"),
+                dir.join("shared"),
+                PathBuf::from("/tmp"),
+                home.join("notes"),
+            ]
+        );
+
+        assert_eq!(
+            config.verification.as_ref().unwrap()["ghcr.io/"].public_keys,
+            Some(vec![dir.join("team/keys/ghcr.pub")])
+        );
+        assert_eq!(
+            plugin("remote").verification.unwrap().public_keys,
+            Some(vec![home.join("cosign.pub")])
+        );
+    }
+
     #[test]
     fn test_load_invalid_structure() {
         let rt = Runtime::new().unwrap();
@@ -686,6 +2015,21 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_auth_config_identity_token_round_trip() {
+        let json = r#"{"type":"identity_token","identity_token":"refresh-123"}"#;
+        let auth_config: AuthConfig = serde_json::from_str(json).unwrap();
+
+        match &auth_config {
+            AuthConfig::IdentityToken { identity_token } => {
+                assert_eq!(identity_token, "refresh-123");
+            }
+            _ => panic!("Expected IdentityToken auth config"),
+        }
+        assert_eq!(serde_json::to_string(&auth_config).unwrap(), json);
+        assert!(!format!("{auth_config:?}").contains("refresh-123"));
+    }
+
     #[test]
     fn test_auth_config_yaml_basic_deserialization() {
         let yaml = r#"
@@ -825,6 +2169,34 @@ plugins:
         assert!(auths.contains_key(&secure_url));
     }
 
+    #[test]
+    fn test_runtime_config_sampling_deserialization() {
+        let yaml = r#"
+plugins:
+  sampling_plugin:
+    url: "file:///path/to/plugin"
+    runtime_config:
+      allow_sampling: true
+      max_sampling_requests: 3
+  default_plugin:
+    url: "file:///path/to/plugin"
+    runtime_config:
+      memory_limit: "1GB"
+"#;
+
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+
+        let sampling_plugin = &config.plugins[&PluginName("sampling_plugin".to_string())];
+        let runtime_config = sampling_plugin.runtime_config.as_ref().unwrap();
+        assert_eq!(runtime_config.allow_sampling, Some(true));
+        assert_eq!(runtime_config.max_sampling_requests, Some(3));
+
+        let default_plugin = &config.plugins[&PluginName("default_plugin".to_string())];
+        let runtime_config = default_plugin.runtime_config.as_ref().unwrap();
+        assert!(runtime_config.allow_sampling.is_none());
+        assert!(runtime_config.max_sampling_requests.is_none());
+    }
+
     #[test]
     fn test_config_without_auths() {
         let json = r#"
@@ -867,7 +2239,515 @@ plugins:
 
         let debug_str = format!("{:?}", auth_config);
         assert!(debug_str.contains("Token"));
-        assert!(debug_str.contains("secret-token"));
+        assert!(!debug_str.contains("secret-token"));
+        assert!(debug_str.contains("<redacted>"));
+    }
+
+    #[test]
+    fn test_config_schema_is_up_to_date() {
+        let committed: serde_json::Value =
+            serde_json::from_str(include_str!("../config.schema.json")).unwrap();
+        assert_eq!(
+            serde_json::to_value(config_schema()).unwrap(),
+            committed,
+            "config.schema.json is out of date, regenerate it with `hyper-mcp config schema > config.schema.json`"
+        );
+    }
+
+    #[test]
+    fn test_config_schema_matches_serde_fields() {
+        let schema = serde_json::to_value(config_schema()).unwrap();
+        let schema_fields = |schema: &serde_json::Value| {
+            let mut fields: Vec<String> = schema["properties"]
+                .as_object()
+                .unwrap()
+                .keys()
+                .cloned()
+                .collect();
+            fields.sort();
+            fields
+        };
+        let serde_fields = |value: serde_json::Value| {
+            let mut fields: Vec<String> = value.as_object().unwrap().keys().cloned().collect();
+            fields.sort();
+            fields
+        };
+
+        let config = Config {
+            include: Some(Vec::new()),
+            auths: None,
+            plugins: HashMap::new(),
+            ..Default::default()
+        };
+        assert_eq!(
+            schema_fields(&schema),
+            serde_fields(serde_json::to_value(&config).unwrap())
+        );
+
+        let plugin_config = PluginConfig {
+            url: Url::parse("oci://ghcr.io/tuananh/time-plugin:latest").unwrap(),
+            runtime_config: None,
+            verification: None,
+        };
+        let mut plugin_fields = serde_fields(serde_json::to_value(&plugin_config).unwrap());
+        plugin_fields.push("path".to_string());
+        plugin_fields.sort();
+        assert_eq!(
+            schema_fields(&schema["$defs"]["PluginConfig"]),
+            plugin_fields
+        );
+
+        assert_eq!(
+            schema_fields(&schema["$defs"]["RuntimeConfig"]),
+            serde_fields(serde_json::to_value(RuntimeConfig::default()).unwrap())
+        );
+
+        let plugin_name_pattern = schema["properties"]["plugins"]["patternProperties"]
+            .as_object()
+            .unwrap()
+            .keys()
+            .next()
+            .unwrap()
+            .clone();
+        assert_eq!(plugin_name_pattern, PLUGIN_NAME_REGEX.as_str());
+
+        let auth_types: Vec<&str> = schema["$defs"]["AuthConfig"]["oneOf"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|variant| variant["properties"]["type"]["const"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            auth_types,
+            vec!["basic", "keyring", "token", "identity_token"]
+        );
+    }
+
+    #[test]
+    fn test_registries() {
+        let rt = Runtime::new().unwrap();
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        std::fs::write(
+            dir.join("mirrors.yaml"),
+            r#"
+registries:
+  insecure: ["mirror.local:5000"]
+  mirrors:
+    "ghcr.io/tuananh/": "mirror.local:5000/tuananh/"
+    "ghcr.io/tuananh/internal/": "registry.internal.example.com/"
+"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("config.yaml"),
+            r#"
+include: ["mirrors.yaml"]
+registries:
+  insecure: ["localhost:5000"]
+  ca_certs: ["certs/ca.pem"]
+"#,
+        )
+        .unwrap();
+
+        let config = rt.block_on(load_config(&dir.join("config.yaml"))).unwrap();
+        let registries = config.registries.unwrap();
+        assert_eq!(
+            registries.insecure.as_deref().unwrap(),
+            ["localhost:5000", "mirror.local:5000"]
+        );
+        assert_eq!(
+            registries.ca_certs.as_deref().unwrap(),
+            [dir.join("certs/ca.pem")]
+        );
+
+        // The longest prefix is replaced.
+        assert_eq!(
+            registries.mirror("ghcr.io/tuananh/time-plugin:latest"),
+            "mirror.local:5000/tuananh/time-plugin:latest"
+        );
+        assert_eq!(
+            registries.mirror("ghcr.io/tuananh/internal/tool:1.0"),
+            "registry.internal.example.com/tool:1.0"
+        );
+        assert_eq!(
+            registries.mirror("quay.io/org/plugin:latest"),
+            "quay.io/org/plugin:latest"
+        );
+
+        // Prefixes only match whole path segments.
+        let registries: RegistriesConfig = serde_yaml::from_str(
+            r#"
+mirrors:
+  "ghcr.io/org": "mirror.local:5000/org"
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            registries.mirror("ghcr.io/org/tool:1.0"),
+            "mirror.local:5000/org/tool:1.0"
+        );
+        assert_eq!(
+            registries.mirror("ghcr.io/org@sha256:abc"),
+            "mirror.local:5000/org@sha256:abc"
+        );
+        assert_eq!(
+            registries.mirror("ghcr.io/org-evil/tool:1.0"),
+            "ghcr.io/org-evil/tool:1.0"
+        );
+
+        assert!(serde_yaml::from_str::<RegistriesConfig>("plain_http: [localhost]\n").is_err());
+    }
+
+    #[test]
+    fn test_verification_for() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+verification:
+  "ghcr.io/":
+    identities:
+      - subject: "me@example.com"
+  "ghcr.io/internal/":
+    skip: true
+plugins:
+  public:
+    url: "oci://ghcr.io/tuananh/time-plugin:latest"
+  internal:
+    url: "oci://ghcr.io/internal/tool:1.0"
+  own:
+    url: "oci://ghcr.io/internal/signed:1.0"
+    verification:
+      identities:
+        - issuer: "https://token.actions.githubusercontent.com"
+          subject: "https://github.com/internal/.+"
+  elsewhere:
+    url: "oci://quay.io/org/plugin:latest"
+"#,
+        )
+        .unwrap();
+        let verification_for = |name: &str| {
+            config.verification_for(&config.plugins[&PluginName::from_str(name).unwrap()])
+        };
+
+        let public = verification_for("public").unwrap();
+        assert_eq!(
+            public.identities.as_ref().unwrap()[0].subject,
+            "me@example.com"
+        );
+        assert_eq!(public.skip, None);
+        assert_eq!(verification_for("internal").unwrap().skip, Some(true));
+        let own = verification_for("own").unwrap();
+        assert_eq!(own.skip, None);
+        assert_eq!(
+            own.identities.as_ref().unwrap()[0].issuer.as_deref(),
+            Some("https://token.actions.githubusercontent.com")
+        );
+        assert!(verification_for("elsewhere").is_none());
+
+        // Prefixes only match whole path segments.
+        let config: Config = serde_yaml::from_str(
+            r#"
+verification:
+  "ghcr.io/org":
+    skip: true
+plugins:
+  own:
+    url: "oci://ghcr.io/org/tool:1.0"
+  tagged:
+    url: "oci://ghcr.io/org:1.0"
+  sibling:
+    url: "oci://ghcr.io/org-evil/tool:1.0"
+"#,
+        )
+        .unwrap();
+        let verification_for = |name: &str| {
+            config.verification_for(&config.plugins[&PluginName::from_str(name).unwrap()])
+        };
+        assert_eq!(verification_for("own").unwrap().skip, Some(true));
+        assert_eq!(verification_for("tagged").unwrap().skip, Some(true));
+        assert!(verification_for("sibling").is_none());
+
+        assert!(serde_yaml::from_str::<VerificationConfig>("skip: true\nmode: magic\n").is_err());
+    }
+
+    #[test]
+    fn test_allowed_path_parsing() {
+        let parse = |s: &str| s.parse::<AllowedPath>();
+        let allowed_path = |host: &str, guest: Option<&str>, read_only| AllowedPath {
+            host: host.to_string(),
+            guest: guest.map(str::to_string),
+            read_only,
+        };
+
+        assert_eq!(parse("/data"), Ok(allowed_path("/data", None, false)));
+        assert_eq!(parse("/data:ro"), Ok(allowed_path("/data", None, true)));
+        assert_eq!(parse("/data:rw"), Ok(allowed_path("/data", None, false)));
+        assert_eq!(
+            parse("./project:/workspace"),
+            Ok(allowed_path("./project", Some("/workspace"), false))
+        );
+        assert_eq!(
+            parse("~/db:/db:ro"),
+            Ok(allowed_path("~/db", Some("/db"), true))
+        );
+        assert_eq!(parse("/data").unwrap().guest(), "/data");
+        assert_eq!(parse("/data:/mnt").unwrap().guest(), "/mnt");
+        assert!(parse("/a:/b:/c").is_err());
+        assert!(parse(":/b").is_err());
+        assert!(parse("/a::ro").is_err());
+
+        assert_eq!(parse(r"C:\data"), Ok(allowed_path(r"C:\data", None, false)));
+        assert_eq!(
+            parse(r"C:\data:ro"),
+            Ok(allowed_path(r"C:\data", None, true))
+        );
+        assert_eq!(
+            parse(r"C:\data:/data"),
+            Ok(allowed_path(r"C:\data", Some("/data"), false))
+        );
+        assert_eq!(
+            parse("/srv:D:/srv:ro"),
+            Ok(allowed_path("/srv", Some("D:/srv"), true))
+        );
+        assert!(parse(r"C:\a:D:\b:E:\c").is_err());
+
+        let runtime_config: RuntimeConfig = serde_yaml::from_str(
+            r#"
+allowed_paths:
+  - "/tmp:/scratch"
+  - host: "/var/lib/db"
+    guest: "/db"
+    read_only: true
+  - host: "/srv"
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            runtime_config.allowed_paths.unwrap(),
+            vec![
+                allowed_path("/tmp", Some("/scratch"), false),
+                allowed_path("/var/lib/db", Some("/db"), true),
+                allowed_path("/srv", None, false),
+            ]
+        );
+
+        assert!(
+            serde_yaml::from_str::<RuntimeConfig>("allowed_paths:\n  - \"/a:/b:/c\"\n").is_err()
+        );
+    }
+
+    #[test]
+    fn test_runtime_config_debug_redacts_env_vars() {
+        let runtime_config = RuntimeConfig {
+            env_vars: Some(HashMap::from([(
+                "GITLAB_TOKEN".to_string(),
+                "glpat-secret".into(),
+            )])),
+            ..Default::default()
+        };
+
+        let debug_str = format!("{runtime_config:?}");
+        assert!(debug_str.contains("GITLAB_TOKEN"));
+        assert!(!debug_str.contains("glpat-secret"));
+    }
+
+    #[test]
+    fn test_load_config_interpolation() {
+        let rt = Runtime::new().unwrap();
+        let dir = tempfile::TempDir::new().unwrap();
+
+        let secret_path = dir.path().join("gitlab_token");
+        std::fs::write(&secret_path, "file-secret\n").unwrap();
+        // SAFETY: the variable name is unique to this test.
+        unsafe { std::env::set_var("HYPER_MCP_TEST_INTERPOLATION_HOST", "gitlab.example.com") };
+
+        let config_path = dir.path().join("config.yaml");
+        std::fs::write(
+            &config_path,
+            format!(
+                r#"
+plugins:
+  gitlab:
+    url: "oci://ghcr.io/tuananh/gitlab-plugin:latest"
+    runtime_config:
+      allowed_hosts:
+        - "${{env:HYPER_MCP_TEST_INTERPOLATION_HOST}}"
+      env_vars:
+        GITLAB_URL: "https://${{env:HYPER_MCP_TEST_INTERPOLATION_HOST}}/api"
+        GITLAB_TOKEN: "${{file:{}}}"
+        TEMPLATE: "$${{env:NOT_RESOLVED}}"
+"#,
+                secret_path.display()
+            ),
+        )
+        .unwrap();
+
+        let config = rt.block_on(load_config(&config_path)).unwrap();
+        let runtime_config = config.plugins[&PluginName::try_from("gitlab").unwrap()]
+            .runtime_config
+            .clone()
+            .unwrap();
+        assert_eq!(
+            runtime_config.allowed_hosts.as_deref().unwrap(),
+            ["gitlab.example.com"]
+        );
+        let env_vars = runtime_config.resolve_env_vars().unwrap();
+        assert_eq!(env_vars["GITLAB_URL"], "https://gitlab.example.com/api");
+        assert_eq!(env_vars["GITLAB_TOKEN"], "file-secret");
+        assert_eq!(env_vars["TEMPLATE"], "${env:NOT_RESOLVED}");
+    }
+
+    #[test]
+    fn test_load_config_interpolation_errors() {
+        let rt = Runtime::new().unwrap();
+        let dir = tempfile::TempDir::new().unwrap();
+        let config_path = dir.path().join("config.json");
+
+        let cases = [
+            (
+                "${env:HYPER_MCP_TEST_DEFINITELY_UNSET}",
+                "Environment variable HYPER_MCP_TEST_DEFINITELY_UNSET is not set",
+            ),
+            (
+                "${file:/definitely/not/a/secret}",
+                "Failed to read secret file /definitely/not/a/secret",
+            ),
+            ("${vault:secret/token}", "Unknown provider vault"),
+            ("${TOKEN}", "Invalid reference ${TOKEN}"),
+        ];
+        for (reference, expected) in cases {
+            std::fs::write(
+                &config_path,
+                format!(
+                    r#"{{"plugins": {{"test": {{"url": "file:///plugin.wasm", "runtime_config": {{"env_vars": {{"TOKEN": "{reference}"}}}}}}}}}}"#
+                ),
+            )
+            .unwrap();
+
+            let error = format!("{:#}", rt.block_on(load_config(&config_path)).unwrap_err());
+            assert!(
+                error.contains("plugins.test.runtime_config.env_vars.TOKEN"),
+                "Error should name the config value: {error}"
+            );
+            assert!(error.contains(expected), "Unexpected error: {error}");
+        }
+    }
+
+    #[test]
+    fn test_load_config_resolves_secret_files() {
+        let rt = Runtime::new().unwrap();
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path().canonicalize().unwrap();
+        std::fs::create_dir_all(dir.join("team/secrets")).unwrap();
+        std::fs::write(dir.join("team/secrets/token"), "included-token\n").unwrap();
+        std::fs::write(dir.join("team/secrets/api_key"), "included-api-key\n").unwrap();
+        std::fs::write(dir.join("config.yaml"), "include:\n  - team/gitlab.yaml\n").unwrap();
+        std::fs::write(
+            dir.join("team/gitlab.yaml"),
+            r#"
+plugins:
+  gitlab:
+    url: "oci://ghcr.io/tuananh/gitlab-plugin:latest"
+    runtime_config:
+      env_vars:
+        GITLAB_TOKEN: "${file:secrets/token}"
+        API_KEY:
+          file: "secrets/api_key"
+"#,
+        )
+        .unwrap();
+
+        // Relative secret files are found next to the fragment naming them,
+        // not in the working directory.
+        let config = rt.block_on(load_config(&dir.join("config.yaml"))).unwrap();
+        let runtime_config = config.plugins[&PluginName::try_from("gitlab").unwrap()]
+            .runtime_config
+            .clone()
+            .unwrap();
+        assert_eq!(
+            runtime_config.env_vars.as_ref().unwrap()["API_KEY"],
+            EnvVarValue::Source(SecretSource::File(dir.join("team/secrets/api_key")))
+        );
+        let env_vars = runtime_config.resolve_env_vars().unwrap();
+        assert_eq!(env_vars["GITLAB_TOKEN"], "included-token");
+        assert_eq!(env_vars["API_KEY"], "included-api-key");
+    }
+
+    #[test]
+    fn test_runtime_config_env_var_sources() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let secret_path = dir.path().join("api_key");
+        std::fs::write(&secret_path, "file-api-key\n").unwrap();
+        // SAFETY: the variable name is unique to this test.
+        unsafe { std::env::set_var("HYPER_MCP_TEST_ENV_VAR_SOURCE", "env-token") };
+
+        let yaml = format!(
+            r#"
+env_vars:
+  LITERAL: "plain-value"
+  TOKEN:
+    env: HYPER_MCP_TEST_ENV_VAR_SOURCE
+  API_KEY:
+    file: "{}"
+"#,
+            secret_path.display()
+        );
+        let runtime_config: RuntimeConfig = serde_yaml::from_str(&yaml).unwrap();
+        let env_vars = runtime_config.resolve_env_vars().unwrap();
+        assert_eq!(env_vars["LITERAL"], "plain-value");
+        assert_eq!(env_vars["TOKEN"], "env-token");
+        assert_eq!(env_vars["API_KEY"], "file-api-key");
+
+        // The config keeps the sources, not what they resolve to.
+        let serialized = serde_json::to_value(&runtime_config).unwrap();
+        assert_eq!(
+            serialized["env_vars"],
+            serde_json::json!({
+                "LITERAL": "plain-value",
+                "TOKEN": {"env": "HYPER_MCP_TEST_ENV_VAR_SOURCE"},
+                "API_KEY": {"file": secret_path},
+            })
+        );
+        assert!(!format!("{serialized}").contains("env-token"));
+        let round_trip: RuntimeConfig = serde_json::from_value(serialized).unwrap();
+        assert_eq!(round_trip.env_vars, runtime_config.env_vars);
+
+        let runtime_config: RuntimeConfig = serde_json::from_str("{}").unwrap();
+        assert!(runtime_config.env_vars.is_none());
+    }
+
+    #[test]
+    fn test_runtime_config_env_var_source_errors() {
+        // Sources are only read when resolved, not when the config is parsed.
+        let cases = [
+            (
+                r#"{"env_vars": {"TOKEN": {"env": "HYPER_MCP_TEST_DEFINITELY_UNSET"}}}"#,
+                "env_vars.TOKEN: Environment variable HYPER_MCP_TEST_DEFINITELY_UNSET is not set",
+            ),
+            (
+                r#"{"env_vars": {"TOKEN": {"file": "/definitely/not/a/secret"}}}"#,
+                "env_vars.TOKEN: Failed to read secret file /definitely/not/a/secret",
+            ),
+        ];
+        for (json, expected) in cases {
+            let runtime_config = serde_json::from_str::<RuntimeConfig>(json).unwrap();
+            let error = format!("{:#}", runtime_config.resolve_env_vars().unwrap_err());
+            assert!(error.contains(expected), "Unexpected error: {error}");
+        }
+
+        let cases = [
+            r#"{"env_vars": {"TOKEN": {"vault": "secret/token"}}}"#,
+            r#"{"env_vars": {"TOKEN": {"keyring": {"service": "hyper-mcp"}}}}"#,
+        ];
+        for json in cases {
+            let error = serde_json::from_str::<RuntimeConfig>(json)
+                .unwrap_err()
+                .to_string();
+            assert!(
+                error.contains("env_vars.TOKEN: expected a string"),
+                "Unexpected error: {error}"
+            );
+        }
     }
 
     #[test]
@@ -1030,6 +2910,7 @@ plugins:
         let config = Config {
             auths: Some(auths),
             plugins: HashMap::new(),
+            ..Default::default()
         };
 
         // Serialize and deserialize to test round-trip
@@ -1227,7 +3108,10 @@ plugins:
 
         let myip_plugin = &config.plugins[&PluginName("myip".to_string())];
         let runtime_config = myip_plugin.runtime_config.as_ref().unwrap();
-        assert_eq!(runtime_config.env_vars.as_ref().unwrap()["FOO"], "bar");
+        assert_eq!(
+            runtime_config.env_vars.as_ref().unwrap()["FOO"],
+            "bar".into()
+        );
         assert_eq!(runtime_config.memory_limit.as_ref().unwrap(), "512Mi");
     }
 
@@ -1475,6 +3359,9 @@ plugins:
                         Ok(AuthConfig::Token { .. }) => {
                             panic!("Expected Basic auth from keyring, got Token");
                         }
+                        Ok(AuthConfig::IdentityToken { .. }) => {
+                            panic!("Expected Basic auth from keyring, got IdentityToken");
+                        }
                         Err(e) => {
                             println!(
                                 "Keyring lookup failed (this is expected if keyring service is not available): {}",
@@ -2159,6 +4046,7 @@ plugins:
         let config = Config {
             auths: Some(auths),
             plugins: HashMap::new(),
+            ..Default::default()
         };
 
         // Test serialization and deserialization round-trip
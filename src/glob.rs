@@ -0,0 +1,41 @@
+//! Patterns where `*` matches any characters, as used by `env_passthrough`,
+//! `allowed_tools` and the hosts a plugin declares.
+
+use regex::Regex;
+
+pub struct Glob(Regex);
+
+impl Glob {
+    pub fn new(pattern: &str) -> Self {
+        let pattern = regex::escape(pattern).replace(r"\*", ".*");
+        Self(Regex::new(&format!("^{pattern}$")).expect("Escaped pattern is a valid regex"))
+    }
+
+    pub fn is_match(&self, value: &str) -> bool {
+        self.0.is_match(value)
+    }
+}
+
+/// Whether `value` matches `pattern`.
+pub fn matches(pattern: &str, value: &str) -> bool {
+    Glob::new(pattern).is_match(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("GITLAB_TOKEN", "GITLAB_TOKEN"));
+        assert!(!matches("GITLAB_TOKEN", "GITLAB_TOKEN_2"));
+        assert!(matches("QDRANT_*", "QDRANT_URL"));
+        assert!(matches("*.example.com", "api.example.com"));
+        assert!(!matches("*.example.com", "example.com"));
+        assert!(matches("arxiv-*_pdf", "arxiv-download_pdf"));
+        assert!(matches("*", ""));
+        // Everything but `*` is literal.
+        assert!(!matches("a.c", "abc"));
+        assert!(matches("a.c+", "a.c+"));
+    }
+}
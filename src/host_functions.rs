@@ -0,0 +1,514 @@
+use crate::{config::PluginName, glob, plugins::PluginService};
+use anyhow::anyhow;
+use extism::{CurrentPlugin, Function, PTR, UserData, Val, convert::Json};
+use rmcp::{
+    Peer, RoleServer,
+    model::{
+        CallToolRequestParam, CreateElicitationRequestParam, CreateMessageRequestParam,
+        ProgressNotificationParam, ProgressToken, RequestId,
+    },
+};
+use serde::Deserialize;
+use tokio_util::sync::CancellationToken;
+
+/// Sampling requests allowed per tool call when `max_sampling_requests` isn't set.
+pub const DEFAULT_MAX_SAMPLING_REQUESTS: u32 = 5;
+
+/// How many plugins deep a chain of `call_tool` host calls may go.
+pub const MAX_TOOL_CALL_DEPTH: usize = 4;
+
+/// State of the tool call a plugin is currently executing.
+///
+/// The plugin's host functions share this through `UserData`. It is filled in
+/// right before `call` runs and cleared again once it returns, so host
+/// functions invoked outside of a tool call (e.g. during `describe`) see the
+/// default, empty context.
+#[derive(Clone, Default)]
+pub struct CallContext {
+    pub request_id: Option<RequestId>,
+    pub peer: Option<Peer<RoleServer>>,
+    pub progress_token: Option<ProgressToken>,
+    // Cancelled by rmcp when the client cancels the request, even before the
+    // call got to the plugin.
+    pub cancellation: Option<CancellationToken>,
+    pub allow_sampling: bool,
+    pub sampling_requests_remaining: u32,
+    pub service: Option<PluginService>,
+    // Plugins currently executing this chain of calls, outermost first.
+    pub call_stack: Vec<PluginName>,
+    pub allowed_tools: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PluginProgress {
+    progress: f64,
+    total: Option<f64>,
+    message: Option<String>,
+}
+
+/// Builds the host functions made available to every plugin.
+pub fn functions(call_context: &UserData<CallContext>) -> Vec<Function> {
+    vec![
+        Function::new(
+            "notify_progress",
+            [PTR],
+            [],
+            call_context.clone(),
+            notify_progress,
+        ),
+        Function::new(
+            "create_message",
+            [PTR],
+            [PTR],
+            call_context.clone(),
+            create_message,
+        ),
+        Function::new("elicit", [PTR], [PTR], call_context.clone(), elicit),
+        Function::new("call_tool", [PTR], [PTR], call_context.clone(), call_tool),
+    ]
+}
+
+/// Whether `tool_name` (namespaced, e.g. `fetch-fetch`) matches an entry of
+/// `allowed_tools`, where `*` matches any characters.
+pub fn is_tool_allowed(allowed_tools: &[String], tool_name: &str) -> bool {
+    allowed_tools
+        .iter()
+        .any(|allowed| glob::matches(allowed, tool_name))
+}
+
+/// `notify_progress(Json<{progress, total?, message?}>)`
+///
+/// Forwards progress to the client as `notifications/progress`. It's a no-op
+/// when the originating `tools/call` did not include a `progressToken`.
+fn notify_progress(
+    plugin: &mut CurrentPlugin,
+    inputs: &[Val],
+    _outputs: &mut [Val],
+    call_context: UserData<CallContext>,
+) -> Result<(), extism::Error> {
+    let Json(progress): Json<PluginProgress> = plugin.memory_get_val(&inputs[0])?;
+    let (peer, progress_token) = {
+        let call_context = call_context.get()?;
+        let call_context = call_context.lock().unwrap();
+        (
+            call_context.peer.clone(),
+            call_context.progress_token.clone(),
+        )
+    };
+
+    let (Some(peer), Some(progress_token)) = (peer, progress_token) else {
+        log::debug!("Dropping progress notification, no progress token in request");
+        return Ok(());
+    };
+
+    tokio::runtime::Handle::current().block_on(peer.notify_progress(
+        ProgressNotificationParam {
+            progress_token,
+            progress: progress.progress,
+            total: progress.total,
+            message: progress.message,
+        },
+    ))?;
+    Ok(())
+}
+
+/// `create_message(Json<CreateMessageRequestParam>) -> Json<CreateMessageResult>`
+///
+/// Asks the client to run an LLM completion via `sampling/createMessage`. Only
+/// available to plugins with `allow_sampling` set, and at most
+/// `max_sampling_requests` times per tool call.
+fn create_message(
+    plugin: &mut CurrentPlugin,
+    inputs: &[Val],
+    outputs: &mut [Val],
+    call_context: UserData<CallContext>,
+) -> Result<(), extism::Error> {
+    let Json(params): Json<CreateMessageRequestParam> = plugin.memory_get_val(&inputs[0])?;
+    let peer = sampling_peer(&mut call_context.get()?.lock().unwrap())?;
+    let result = tokio::runtime::Handle::current().block_on(peer.create_message(params))?;
+    let handle = plugin.memory_new(Json(result))?;
+    outputs[0] = plugin.memory_to_val(handle);
+    Ok(())
+}
+
+/// The client to send a sampling request of the current tool call to, once
+/// it's counted against the call's `max_sampling_requests`.
+fn sampling_peer(call_context: &mut CallContext) -> anyhow::Result<Peer<RoleServer>> {
+    if !call_context.allow_sampling {
+        return Err(anyhow!("Sampling is not enabled for this plugin"));
+    }
+    let peer = call_context
+        .peer
+        .clone()
+        .ok_or_else(|| anyhow!("Sampling is only available during a tool call"))?;
+    if peer
+        .peer_info()
+        .is_none_or(|info| info.capabilities.sampling.is_none())
+    {
+        return Err(anyhow!("Client does not support sampling"));
+    }
+    if call_context.sampling_requests_remaining == 0 {
+        return Err(anyhow!("Sampling request limit reached for this tool call"));
+    }
+    call_context.sampling_requests_remaining -= 1;
+    Ok(peer)
+}
+
+/// `elicit(Json<CreateElicitationRequestParam>) -> Json<CreateElicitationResult>`
+///
+/// Asks the user for input through the client (`elicitation/create`) and
+/// suspends the call until they answer. Declining or cancelling is not an
+/// error, the plugin gets the `action` the user chose.
+fn elicit(
+    plugin: &mut CurrentPlugin,
+    inputs: &[Val],
+    outputs: &mut [Val],
+    call_context: UserData<CallContext>,
+) -> Result<(), extism::Error> {
+    let Json(params): Json<CreateElicitationRequestParam> = plugin.memory_get_val(&inputs[0])?;
+    let peer = elicitation_peer(&call_context.get()?.lock().unwrap())?;
+    let result = tokio::runtime::Handle::current().block_on(peer.create_elicitation(params))?;
+    let handle = plugin.memory_new(Json(result))?;
+    outputs[0] = plugin.memory_to_val(handle);
+    Ok(())
+}
+
+/// The client to ask for input during the current tool call.
+fn elicitation_peer(call_context: &CallContext) -> anyhow::Result<Peer<RoleServer>> {
+    let peer = call_context
+        .peer
+        .clone()
+        .ok_or_else(|| anyhow!("Elicitation is only available during a tool call"))?;
+    if !peer.supports_elicitation() {
+        return Err(anyhow!("Client does not support elicitation"));
+    }
+    Ok(peer)
+}
+
+/// `call_tool(Json<CallToolRequestParam>) -> Json<CallToolResult>`
+///
+/// Calls another plugin's tool by its namespaced name, through the same path
+/// as a client `tools/call`. The tool must be listed in the caller's
+/// `allowed_tools`. A plugin already running further up the chain can't be
+/// called again, since it's still holding its own instance.
+fn call_tool(
+    plugin: &mut CurrentPlugin,
+    inputs: &[Val],
+    outputs: &mut [Val],
+    call_context: UserData<CallContext>,
+) -> Result<(), extism::Error> {
+    let Json(request): Json<CallToolRequestParam> = plugin.memory_get_val(&inputs[0])?;
+    let (service, nested_context) =
+        nested_call_context(&call_context.get()?.lock().unwrap(), &request.name)?;
+
+    let result = tokio::runtime::Handle::current()
+        .block_on(service.call_tool(request, nested_context))
+        .map_err(|e| anyhow!("{e}"))?;
+    let handle = plugin.memory_new(Json(result))?;
+    outputs[0] = plugin.memory_to_val(handle);
+    Ok(())
+}
+
+/// The service to call `tool_name` through and the context of that call,
+/// if the plugin running `call_context` may call it.
+pub(crate) fn nested_call_context(
+    call_context: &CallContext,
+    tool_name: &str,
+) -> anyhow::Result<(PluginService, CallContext)> {
+    // A plain clone would start a new session, without this request's
+    // in-flight calls.
+    let service = call_context
+        .service
+        .as_ref()
+        .map(PluginService::session_clone)
+        .ok_or_else(|| anyhow!("Tool calls are only available during a tool call"))?;
+    if !is_tool_allowed(&call_context.allowed_tools, tool_name) {
+        return Err(anyhow!(
+            "Tool {tool_name} is not in allowed_tools for this plugin"
+        ));
+    }
+    if let Some((target, _)) = tool_name.split_once('-') {
+        if call_context.call_stack.iter().any(|p| p.as_str() == target) {
+            return Err(anyhow!(
+                "Plugin {target} is already running in this call chain, refusing to call {tool_name}"
+            ));
+        }
+    }
+    if call_context.call_stack.len() >= MAX_TOOL_CALL_DEPTH {
+        return Err(anyhow!(
+            "Tool call depth limit of {MAX_TOOL_CALL_DEPTH} reached, refusing to call {tool_name}"
+        ));
+    }
+    let nested_context = CallContext {
+        request_id: call_context.request_id.clone(),
+        peer: call_context.peer.clone(),
+        call_stack: call_context.call_stack.clone(),
+        ..Default::default()
+    };
+    Ok((service, nested_context))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_client::{TestClient, connect};
+    use clap::Parser;
+    use extism::ValType;
+    use rmcp::model::{CreateElicitationResult, ElicitationAction};
+
+    #[test]
+    fn test_functions_registers_notify_progress() {
+        let call_context = UserData::new(CallContext::default());
+        let functions = functions(&call_context);
+
+        let notify_progress = functions
+            .iter()
+            .find(|f| f.name() == "notify_progress")
+            .expect("notify_progress should be registered");
+        assert_eq!(notify_progress.params(), &[ValType::I64]);
+        assert!(notify_progress.results().is_empty());
+
+        let create_message = functions
+            .iter()
+            .find(|f| f.name() == "create_message")
+            .expect("create_message should be registered");
+        assert_eq!(create_message.params(), &[ValType::I64]);
+        assert_eq!(create_message.results(), &[ValType::I64]);
+
+        let elicit = functions
+            .iter()
+            .find(|f| f.name() == "elicit")
+            .expect("elicit should be registered");
+        assert_eq!(elicit.params(), &[ValType::I64]);
+        assert_eq!(elicit.results(), &[ValType::I64]);
+
+        let call_tool = functions
+            .iter()
+            .find(|f| f.name() == "call_tool")
+            .expect("call_tool should be registered");
+        assert_eq!(call_tool.params(), &[ValType::I64]);
+        assert_eq!(call_tool.results(), &[ValType::I64]);
+    }
+
+    #[test]
+    fn test_call_context_default_is_empty() {
+        let call_context = CallContext::default();
+        assert!(call_context.request_id.is_none());
+        assert!(call_context.peer.is_none());
+        assert!(call_context.progress_token.is_none());
+        assert!(call_context.cancellation.is_none());
+        assert!(!call_context.allow_sampling);
+        assert_eq!(call_context.sampling_requests_remaining, 0);
+        assert!(call_context.service.is_none());
+        assert!(call_context.call_stack.is_empty());
+        assert!(call_context.allowed_tools.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_sampling_peer() {
+        let (peer, _client) = connect(TestClient {
+            sampling: true,
+            ..Default::default()
+        })
+        .await;
+
+        let mut call_context = CallContext {
+            peer: Some(peer),
+            sampling_requests_remaining: 2,
+            ..Default::default()
+        };
+        let error = sampling_peer(&mut call_context).unwrap_err();
+        assert_eq!(error.to_string(), "Sampling is not enabled for this plugin");
+        assert_eq!(call_context.sampling_requests_remaining, 2);
+
+        call_context.allow_sampling = true;
+        for remaining in [1, 0] {
+            let peer = sampling_peer(&mut call_context).unwrap();
+            assert_eq!(call_context.sampling_requests_remaining, remaining);
+            let result = peer
+                .create_message(CreateMessageRequestParam {
+                    messages: Vec::new(),
+                    model_preferences: None,
+                    system_prompt: None,
+                    include_context: None,
+                    temperature: None,
+                    max_tokens: 10,
+                    stop_sequences: None,
+                    metadata: None,
+                })
+                .await
+                .unwrap();
+            assert_eq!(result.model, "test-model");
+        }
+        let error = sampling_peer(&mut call_context).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Sampling request limit reached for this tool call"
+        );
+
+        let mut outside_call = CallContext {
+            allow_sampling: true,
+            sampling_requests_remaining: 5,
+            ..Default::default()
+        };
+        let error = sampling_peer(&mut outside_call).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Sampling is only available during a tool call"
+        );
+
+        let (peer, _client) = connect(TestClient::default()).await;
+        let mut without_sampling = CallContext {
+            peer: Some(peer),
+            allow_sampling: true,
+            sampling_requests_remaining: 5,
+            ..Default::default()
+        };
+        let error = sampling_peer(&mut without_sampling).unwrap_err();
+        assert_eq!(error.to_string(), "Client does not support sampling");
+        // Nothing was sent, so nothing counts against the limit.
+        assert_eq!(without_sampling.sampling_requests_remaining, 5);
+    }
+
+    #[tokio::test]
+    async fn test_elicitation_peer() {
+        let error = elicitation_peer(&CallContext::default()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Elicitation is only available during a tool call"
+        );
+
+        let (peer, _client) = connect(TestClient::default()).await;
+        let error = elicitation_peer(&CallContext {
+            peer: Some(peer),
+            ..Default::default()
+        })
+        .unwrap_err();
+        assert_eq!(error.to_string(), "Client does not support elicitation");
+
+        // Whatever the user chose is handed to the plugin as is, declining
+        // isn't an error.
+        for (answer, expected) in [
+            (
+                CreateElicitationResult {
+                    action: ElicitationAction::Decline,
+                    content: None,
+                },
+                serde_json::json!({"action": "decline"}),
+            ),
+            (
+                CreateElicitationResult {
+                    action: ElicitationAction::Accept,
+                    content: Some(serde_json::json!({"name": "hyper-mcp"})),
+                },
+                serde_json::json!({"action": "accept", "content": {"name": "hyper-mcp"}}),
+            ),
+        ] {
+            let (peer, _client) = connect(TestClient {
+                elicitation: Some(answer),
+                ..Default::default()
+            })
+            .await;
+            let peer = elicitation_peer(&CallContext {
+                peer: Some(peer),
+                ..Default::default()
+            })
+            .unwrap();
+            let result = peer
+                .create_elicitation(CreateElicitationRequestParam {
+                    message: "What's your name?".to_string(),
+                    requested_schema: serde_json::from_value(serde_json::json!({
+                        "type": "object",
+                        "properties": {"name": {"type": "string"}}
+                    }))
+                    .unwrap(),
+                })
+                .await
+                .unwrap();
+            assert_eq!(serde_json::to_value(result).unwrap(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_nested_call_context() {
+        let cli = crate::Cli::parse_from(["hyper-mcp"]);
+        let service = PluginService::with_config(Default::default(), &cli)
+            .await
+            .unwrap();
+        let plugin = |name: &str| PluginName::try_from(name).unwrap();
+        let call_context = CallContext {
+            request_id: Some(RequestId::Number(1)),
+            service: Some(service),
+            call_stack: vec![plugin("research")],
+            allowed_tools: vec!["fetch-*".to_string(), "research-search".to_string()],
+            allow_sampling: true,
+            ..Default::default()
+        };
+
+        let (_, nested_context) = nested_call_context(&call_context, "fetch-fetch").unwrap();
+        assert_eq!(nested_context.call_stack, [plugin("research")]);
+        // Cancelling the client's request reaches the nested call too.
+        assert_eq!(nested_context.request_id, Some(RequestId::Number(1)));
+        // Sampling and tool permissions are the called plugin's own.
+        assert!(!nested_context.allow_sampling);
+        assert!(nested_context.allowed_tools.is_empty());
+
+        let error = nested_call_context(&call_context, "memory-store")
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Tool memory-store is not in allowed_tools for this plugin"
+        );
+
+        let error = nested_call_context(&call_context, "research-search")
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Plugin research is already running in this call chain, refusing to call research-search"
+        );
+
+        let deep_context = CallContext {
+            call_stack: (0..MAX_TOOL_CALL_DEPTH)
+                .map(|depth| plugin(&format!("plugin{depth}")))
+                .collect(),
+            ..call_context.clone()
+        };
+        let error = nested_call_context(&deep_context, "fetch-fetch")
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "Tool call depth limit of {MAX_TOOL_CALL_DEPTH} reached, refusing to call fetch-fetch"
+            )
+        );
+
+        let outside_call = CallContext {
+            service: None,
+            ..call_context
+        };
+        let error = nested_call_context(&outside_call, "fetch-fetch")
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Tool calls are only available during a tool call"
+        );
+    }
+
+    #[test]
+    fn test_is_tool_allowed() {
+        let allowed_tools = vec!["fetch-fetch".to_string(), "arxiv-*".to_string()];
+
+        assert!(is_tool_allowed(&allowed_tools, "fetch-fetch"));
+        assert!(is_tool_allowed(&allowed_tools, "arxiv-arxiv_search"));
+        assert!(is_tool_allowed(&allowed_tools, "arxiv-arxiv_download_pdf"));
+        assert!(!is_tool_allowed(&allowed_tools, "fetch-fetch_raw"));
+        assert!(!is_tool_allowed(&allowed_tools, "memory-store_memory"));
+        assert!(!is_tool_allowed(&allowed_tools, "arxiv_mirror-search"));
+        assert!(!is_tool_allowed(&[], "fetch-fetch"));
+        assert!(is_tool_allowed(&["*-search".to_string()], "arxiv-search"));
+    }
+}
@@ -22,6 +22,10 @@ impl Authenticator for RequestBuilder {
                             self.basic_auth(username, Some(password))
                         }
                         AuthConfig::Token { token } => self.bearer_auth(token),
+                        AuthConfig::IdentityToken { .. } => {
+                            log::warn!("Identity tokens only apply to OCI registries, not {k}");
+                            self
+                        }
                     };
                 }
             }
@@ -0,0 +1,159 @@
+use crate::Cli;
+use crate::config::PluginName;
+use crate::plugins::{LoadClients, fetch_plugin, load_cli_config};
+use crate::wasm::custom_section;
+use anyhow::{Context, Result, anyhow};
+use clap::Args;
+use flate2::read::ZlibDecoder;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+
+/// The custom section `cargo auditable` embeds the dependency tree in.
+const AUDITABLE_SECTION: &str = ".dep-v0";
+
+#[derive(Args, Clone, Debug)]
+pub struct InspectArgs {
+    /// A .wasm file, or the name of a plugin in the config
+    pub plugin: String,
+
+    #[arg(long, help = "Print the dependency list as JSON")]
+    pub json: bool,
+}
+
+/// The dependency tree `cargo auditable` records, as in its `.dep-v0`
+/// section.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct AuditableDependencies {
+    pub packages: Vec<Package>,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct Package {
+    pub name: String,
+    pub version: String,
+    pub source: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub root: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<usize>,
+}
+
+/// The dependencies embedded in a plugin built with `cargo auditable`, or
+/// `None` if it wasn't.
+pub fn auditable_dependencies(wasm: &[u8]) -> Result<Option<AuditableDependencies>> {
+    let Some(section) = custom_section(wasm, AUDITABLE_SECTION)? else {
+        return Ok(None);
+    };
+    let mut json = Vec::new();
+    ZlibDecoder::new(section)
+        .read_to_end(&mut json)
+        .context("Invalid cargo auditable section")?;
+    Ok(Some(
+        serde_json::from_slice(&json).context("Invalid cargo auditable dependency list")?,
+    ))
+}
+
+/// The wasm of `plugin`: a file if it exists, otherwise the configured
+/// plugin of that name, fetched like at startup.
+async fn read_plugin(cli: &Cli, plugin: &str) -> Result<Vec<u8>> {
+    let path = Path::new(plugin);
+    if path.is_file()
+        || path
+            .extension()
+            .is_some_and(|extension| extension == "wasm")
+    {
+        return std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()));
+    }
+
+    let config = load_cli_config(cli).await?;
+    let plugin_name = PluginName::from_str(plugin)?;
+    let plugin_cfg = config
+        .plugins
+        .get(&plugin_name)
+        .ok_or_else(|| anyhow!("No file or configured plugin named {plugin}"))?;
+    fetch_plugin(
+        cli,
+        &config,
+        &LoadClients::default(),
+        &plugin_name,
+        plugin_cfg,
+    )
+    .await
+    .map(|fetched| fetched.wasm)
+}
+
+/// `hyper-mcp inspect`
+pub async fn run(cli: &Cli, args: InspectArgs) -> Result<()> {
+    let wasm = read_plugin(cli, &args.plugin).await?;
+    let Some(dependencies) = auditable_dependencies(&wasm)? else {
+        return Err(anyhow!(
+            "{} has no embedded dependency list, build it with `cargo auditable build`",
+            args.plugin
+        ));
+    };
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&dependencies)?);
+        return Ok(());
+    }
+    for package in &dependencies.packages {
+        let kind = match package.kind.as_deref() {
+            Some("build") => " (build)",
+            Some("dev") => " (dev)",
+            _ => "",
+        };
+        println!(
+            "{} {} {}{kind}",
+            package.name, package.version, package.source
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wasm::tests::module_with_sections;
+    use flate2::{Compression, write::ZlibEncoder};
+    use std::io::Write;
+
+    #[test]
+    fn test_auditable_dependencies() {
+        let json = serde_json::json!({
+            "packages": [
+                {
+                    "name": "time",
+                    "version": "0.1.0",
+                    "source": "local",
+                    "root": true,
+                    "dependencies": [1, 2]
+                },
+                { "name": "extism-pdk", "version": "1.4.1", "source": "crates.io" },
+                { "name": "cc", "version": "1.2.0", "source": "crates.io", "kind": "build" }
+            ]
+        });
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(json.to_string().as_bytes()).unwrap();
+        let wasm = module_with_sections(&[(AUDITABLE_SECTION, &encoder.finish().unwrap())]);
+
+        let dependencies = auditable_dependencies(&wasm).unwrap().unwrap();
+        assert_eq!(dependencies.packages.len(), 3);
+        assert_eq!(dependencies.packages[0].name, "time");
+        assert!(dependencies.packages[0].root);
+        assert_eq!(dependencies.packages[0].dependencies, [1, 2]);
+        assert_eq!(dependencies.packages[2].kind.as_deref(), Some("build"));
+        // What `--json` prints reads like the section itself.
+        assert_eq!(serde_json::to_value(&dependencies).unwrap(), json);
+
+        assert_eq!(
+            auditable_dependencies(&module_with_sections(&[])).unwrap(),
+            None
+        );
+        let corrupt = module_with_sections(&[(AUDITABLE_SECTION, b"not zlib")]);
+        assert!(auditable_dependencies(&corrupt).is_err());
+    }
+}
@@ -1,5 +1,5 @@
 use anyhow::Result;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use rmcp::transport::sse_server::SseServer;
 use rmcp::transport::streamable_http_server::{
     StreamableHttpService, session::local::LocalSessionManager,
@@ -8,19 +8,43 @@ use rmcp::{ServiceExt, transport::stdio};
 use std::path::PathBuf;
 use tracing_subscriber::{self, EnvFilter};
 
+mod attestation;
+mod auth;
 mod config;
+mod glob;
+mod host_functions;
 mod https_auth;
+mod inspect;
 mod oci;
+mod permissions;
 mod plugins;
+mod push;
+#[cfg(test)]
+mod test_client;
+#[cfg(test)]
+mod test_registry;
+mod validate;
+mod wasm;
 
 pub const DEFAULT_BIND_ADDRESS: &str = "127.0.0.1:3001";
 
 #[derive(Parser, Clone)]
 #[command(author = "Tuan Anh Tran <me@tuananh.org>", version = env!("CARGO_PKG_VERSION"), about, long_about = None)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
     #[arg(short, long, value_name = "FILE")]
     config_file: Option<PathBuf>,
 
+    #[arg(
+        long = "config-dir",
+        value_name = "DIR",
+        help = "Directory of config fragments (*.yaml, *.yml, *.json, *.toml) merged into the config",
+        env = "HYPER_MCP_CONFIG_DIR"
+    )]
+    config_dir: Option<PathBuf>,
+
     #[arg(
         long = "log-level",
         value_name = "LEVEL",
@@ -54,6 +78,14 @@ struct Cli {
     )]
     insecure_skip_signature: bool,
 
+    #[arg(
+        long = "enforce-permissions",
+        help = "Refuse to load plugins granted more than they declare or missing required config",
+        env = "HYPER_MCP_ENFORCE_PERMISSIONS",
+        default_value = "false"
+    )]
+    enforce_permissions: bool,
+
     #[arg(
         long = "use-sigstore-tuf-data",
         help = "Use Sigstore TUF data for verification",
@@ -76,6 +108,14 @@ struct Cli {
     )]
     fulcio_certs: Option<PathBuf>,
 
+    #[arg(
+        long = "sigstore-trust-root",
+        value_name = "FILE",
+        help = "Path to a Sigstore trusted_root.json to verify keyless signatures with instead of fetching it through TUF",
+        env = "HYPER_MCP_SIGSTORE_TRUST_ROOT"
+    )]
+    sigstore_trust_root: Option<PathBuf>,
+
     #[arg(
         long = "cert-issuer",
         help = "Certificate issuer to verify against",
@@ -96,6 +136,53 @@ struct Cli {
         env = "HYPER_MCP_CERT_URL"
     )]
     cert_url: Option<String>,
+
+    #[arg(
+        long = "cosign-key",
+        value_name = "FILE",
+        help = "PEM public key to verify OCI image signatures with instead of keyless verification, may be repeated",
+        env = "HYPER_MCP_COSIGN_KEYS",
+        value_delimiter = ','
+    )]
+    cosign_keys: Vec<PathBuf>,
+}
+
+#[derive(Subcommand, Clone)]
+enum Commands {
+    /// Manage registry and HTTPS credentials stored in the system keyring
+    Auth(auth::AuthArgs),
+    /// Work with the config file
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Show the dependencies `cargo auditable` embedded in a plugin
+    Inspect(inspect::InspectArgs),
+    /// Publish plugins
+    Plugin {
+        #[command(subcommand)]
+        command: PluginCommand,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+enum ConfigCommand {
+    /// Check the config file for errors and report them with their position
+    Validate {
+        #[arg(
+            long,
+            help = "Also fetch every plugin and check skip_tools against its tools"
+        )]
+        deep: bool,
+    },
+    /// Print the JSON Schema of the config file
+    Schema,
+}
+
+#[derive(Subcommand, Clone)]
+enum PluginCommand {
+    /// Push a .wasm plugin to an OCI registry as a wasm artifact
+    Push(push::PushArgs),
 }
 
 #[tokio::main]
@@ -109,6 +196,27 @@ async fn main() -> Result<()> {
         .with_ansi(false)
         .init();
 
+    match cli.command.clone() {
+        Some(Commands::Auth(args)) => return auth::run(args),
+        Some(Commands::Config {
+            command: ConfigCommand::Validate { deep },
+        }) => return validate::run(&cli, deep).await,
+        Some(Commands::Config {
+            command: ConfigCommand::Schema,
+        }) => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&config::config_schema())?
+            );
+            return Ok(());
+        }
+        Some(Commands::Inspect(args)) => return inspect::run(&cli, args).await,
+        Some(Commands::Plugin {
+            command: PluginCommand::Push(args),
+        }) => return push::run(&cli, args).await,
+        None => {}
+    }
+
     tracing::info!("Starting hyper-mcp server");
 
     // Create plugin service with the CLI options
@@ -1,24 +1,46 @@
 use crate::Cli;
-use anyhow::anyhow;
+use crate::attestation::verify_attestations;
+use crate::config::{AuthConfig, RegistriesConfig, SigningIdentity, VerificationConfig};
+use anyhow::{Context, anyhow};
 use docker_credential::{CredentialRetrievalError, DockerCredential};
 use flate2::read::GzDecoder;
 use oci_client::Reference;
-use oci_client::{Client, manifest, manifest::OciDescriptor, secrets::RegistryAuth};
+use oci_client::{Client, manifest, secrets::RegistryAuth};
+use once_cell::sync::Lazy;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sigstore::bundle::Bundle;
+use sigstore::bundle::verify::{PolicyError, VerificationPolicy, Verifier, policy::PolicyResult};
+use sigstore::cosign::signature_layers::CertificateSubject;
 use sigstore::cosign::verification_constraint::cert_subject_email_verifier::StringVerifier;
 use sigstore::cosign::verification_constraint::{
-    CertSubjectEmailVerifier, CertSubjectUrlVerifier, VerificationConstraintVec,
+    CertSubjectEmailVerifier, CertSubjectUrlVerifier, PublicKeyVerifier, VerificationConstraint,
+    VerificationConstraintVec,
 };
-use sigstore::cosign::{ClientBuilder, CosignCapabilities, verify_constraints};
+use sigstore::cosign::{ClientBuilder, CosignCapabilities, SignatureLayer, verify_constraints};
+use sigstore::crypto::{CosignVerificationKey, Signature};
 use sigstore::errors::SigstoreVerifyConstraintsError;
-use sigstore::registry::{Auth, OciReference};
+use sigstore::registry::{
+    Auth, Certificate as RegistryCertificate, CertificateEncoding,
+    ClientConfig as RegistryClientConfig, ClientProtocol, OciReference,
+};
 use sigstore::trust::sigstore::SigstoreTrustRoot;
 use sigstore::trust::{ManualTrustRoot, TrustRoot};
+use sigstore_protobuf_specs::dev::sigstore::{
+    bundle::v1::bundle::Content,
+    common::v1::TimeRange,
+    trustroot::v1::{TransparencyLogInstance, TrustedRoot},
+};
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::io::Read;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
 use tar::Archive;
+use tokio::sync::OnceCell;
+use url::Url;
 
 // Docker manifest format v2
 #[derive(Debug, Serialize, Deserialize)]
@@ -47,15 +69,232 @@ struct DockerManifestLayer {
     digest: String,
 }
 
-fn build_auth(reference: &Reference) -> RegistryAuth {
+/// An OCI client for pulls, and what the sigstore client looking up
+/// signatures and the token exchange need to reach the same registries.
+pub struct RegistryClient {
+    pub client: Client,
+    pub config: RegistryClientConfig,
+    http: reqwest::Client,
+}
+
+impl RegistryClient {
+    pub fn new(config: RegistryClientConfig) -> Result<Self, anyhow::Error> {
+        let mut http = reqwest::Client::builder();
+        for certificate in &config.extra_root_certificates {
+            http = http.add_root_certificate(reqwest::Certificate::from_der(&certificate.data)?);
+        }
+        Ok(Self {
+            client: Client::new(config.clone().into()),
+            http: http.build()?,
+            config,
+        })
+    }
+
+    /// The base URL of `registry`, e.g. `https://ghcr.io`.
+    fn url(&self, registry: &str) -> String {
+        let insecure = match &self.config.protocol {
+            ClientProtocol::Http => true,
+            ClientProtocol::Https => false,
+            ClientProtocol::HttpsExcept(insecure) => insecure.iter().any(|r| r == registry),
+        };
+        format!("{}://{registry}", if insecure { "http" } else { "https" })
+    }
+}
+
+/// The client settings for `registries`: plain HTTP to the insecure ones,
+/// and trust in the extra CA certificates.
+pub fn registry_client_config(
+    registries: Option<&RegistriesConfig>,
+) -> Result<RegistryClientConfig, anyhow::Error> {
+    let Some(registries) = registries else {
+        return Ok(RegistryClientConfig::default());
+    };
+    let mut extra_root_certificates = Vec::new();
+    for path in registries.ca_certs.iter().flatten() {
+        let pem = fs::read(path)
+            .with_context(|| format!("Failed to read CA certificate {}", path.display()))?;
+        let certificates = x509_cert::Certificate::load_pem_chain(&pem)
+            .map_err(|e| anyhow!("Invalid CA certificate {}: {e}", path.display()))?;
+        for certificate in certificates {
+            extra_root_certificates.push(RegistryCertificate {
+                encoding: CertificateEncoding::Der,
+                data: x509_cert::der::Encode::to_der(&certificate)?,
+            });
+        }
+    }
+    let insecure = registries.insecure.clone().unwrap_or_default();
+    Ok(RegistryClientConfig {
+        protocol: if insecure.is_empty() {
+            ClientProtocol::Https
+        } else {
+            ClientProtocol::HttpsExcept(insecure)
+        },
+        extra_root_certificates,
+        ..Default::default()
+    })
+}
+
+/// The `auths` entry for a repository: the longest key whose host (and port)
+/// is the registry and whose path, if any, is a prefix of the repository.
+/// `https://ghcr.io` covers every repository on ghcr.io, `oci://ghcr.io/org`
+/// only those under `org/`.
+pub(crate) fn find_registry_auth<'a>(
+    auths: &'a HashMap<Url, AuthConfig>,
+    reference: &Reference,
+) -> Option<(&'a Url, &'a AuthConfig)> {
+    let registries = [reference.registry(), reference.resolve_registry()];
+    let repository = reference.repository();
+    auths
+        .iter()
+        .filter(|(url, _)| {
+            let Some(host) = url.host_str() else {
+                return false;
+            };
+            let host = match url.port() {
+                Some(port) => format!("{host}:{port}"),
+                None => host.to_string(),
+            };
+            let path = url.path().trim_matches('/');
+            registries.contains(&host.as_str())
+                && (path.is_empty()
+                    || repository == path
+                    || repository.starts_with(&format!("{path}/")))
+        })
+        .max_by_key(|(url, _)| url.path().trim_matches('/').len())
+}
+
+// `Bearer realm="https://auth.example.com/token",service="registry.example.com"`
+static CHALLENGE_PARAM_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(\w+)="([^"]*)""#).expect("Failed to compile challenge param regex")
+});
+
+/// Realm and service of a `WWW-Authenticate: Bearer ...` challenge.
+fn parse_bearer_challenge(header: &str) -> Option<(String, Option<String>)> {
+    let (scheme, params) = header.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("bearer") {
+        return None;
+    }
+    let params: HashMap<&str, &str> = CHALLENGE_PARAM_REGEX
+        .captures_iter(params)
+        .filter_map(|captures| Some((captures.get(1)?.as_str(), captures.get(2)?.as_str())))
+        .collect();
+    Some((
+        params.get("realm")?.to_string(),
+        params.get("service").map(|service| service.to_string()),
+    ))
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: Option<String>,
+    token: Option<String>,
+}
+
+/// Exchanges an identity token, the OAuth2 refresh token `docker login`
+/// stores for ACR, GCR or Harbor, for an access token for `actions` (`pull`
+/// or `pull,push`) on `repository`. `registry_url` is the registry's base URL, e.g.
+/// `https://myregistry.azurecr.io`.
+async fn exchange_identity_token(
+    client: &reqwest::Client,
+    registry_url: &str,
+    repository: &str,
+    actions: &str,
+    identity_token: &str,
+) -> Result<String, anyhow::Error> {
+    let response = client
+        .get(format!("{registry_url}/v2/"))
+        .send()
+        .await
+        .with_context(|| format!("Failed to reach {registry_url}"))?;
+    let challenge = response
+        .headers()
+        .get(reqwest::header::WWW_AUTHENTICATE)
+        .and_then(|header| header.to_str().ok())
+        .and_then(parse_bearer_challenge);
+    let Some((realm, service)) = challenge else {
+        return Err(anyhow!(
+            "{registry_url} did not answer with a bearer challenge"
+        ));
+    };
+
+    let scope = format!("repository:{repository}:{actions}");
+    let mut form = vec![
+        ("grant_type", "refresh_token"),
+        ("client_id", "hyper-mcp"),
+        ("scope", scope.as_str()),
+        ("refresh_token", identity_token),
+    ];
+    if let Some(service) = &service {
+        form.push(("service", service));
+    }
+    let response: TokenResponse = client
+        .post(&realm)
+        .form(&form)
+        .send()
+        .await
+        .with_context(|| format!("Failed to reach token service {realm}"))?
+        .error_for_status()
+        .with_context(|| format!("Token service {realm} rejected the identity token"))?
+        .json()
+        .await
+        .with_context(|| format!("Invalid response from token service {realm}"))?;
+    response
+        .access_token
+        .or(response.token)
+        .ok_or_else(|| anyhow!("Token service {realm} returned no access token"))
+}
+
+/// Credentials for `actions` (`pull` or `pull,push`) on `reference`: a
+/// matching `auths` entry, then the docker credential helpers and config, then
+/// anonymous access. Identity tokens are exchanged at the registry's token
+/// service; plain tokens are sent as bearer tokens.
+pub(crate) async fn build_auth(
+    registry: &RegistryClient,
+    reference: &Reference,
+    auths: &Option<HashMap<Url, AuthConfig>>,
+    actions: &str,
+) -> RegistryAuth {
     let server = reference
         .resolve_registry()
         .strip_suffix('/')
         .unwrap_or_else(|| reference.resolve_registry());
+    let registry_url = registry.url(server);
 
-    // if cli.anonymous {
-    //     return RegistryAuth::Anonymous;
-    // }
+    match auths
+        .as_ref()
+        .and_then(|auths| find_registry_auth(auths, reference))
+        .map(|(_, auth)| auth)
+    {
+        Some(AuthConfig::Basic { username, password }) => {
+            log::info!("Using credentials from auths for {server}");
+            return RegistryAuth::Basic(username.clone(), password.clone());
+        }
+        Some(AuthConfig::Token { token }) => {
+            log::info!("Using token from auths for {server}");
+            return RegistryAuth::Bearer(token.clone());
+        }
+        Some(AuthConfig::IdentityToken { identity_token }) => {
+            return match exchange_identity_token(
+                &registry.http,
+                &registry_url,
+                reference.repository(),
+                actions,
+                identity_token,
+            )
+            .await
+            {
+                Ok(access_token) => {
+                    log::info!("Exchanged identity token from auths for {server}");
+                    RegistryAuth::Bearer(access_token)
+                }
+                Err(e) => {
+                    log::warn!("Failed to exchange identity token: {e:#}. Using anonymous auth");
+                    RegistryAuth::Anonymous
+                }
+            };
+        }
+        None => {}
+    }
 
     match docker_credential::get_credential(server) {
         Err(CredentialRetrievalError::ConfigNotFound) => RegistryAuth::Anonymous,
@@ -68,23 +307,135 @@ fn build_auth(reference: &Reference) -> RegistryAuth {
             log::info!("Found docker credentials");
             RegistryAuth::Basic(username, password)
         }
-        Ok(DockerCredential::IdentityToken(_)) => {
-            log::info!(
-                "Cannot use contents of docker config, identity token not supported. Using anonymous auth"
-            );
-            RegistryAuth::Anonymous
+        Ok(DockerCredential::IdentityToken(identity_token)) => {
+            match exchange_identity_token(
+                &registry.http,
+                &registry_url,
+                reference.repository(),
+                actions,
+                &identity_token,
+            )
+            .await
+            {
+                Ok(access_token) => {
+                    log::info!("Exchanged docker identity token for {server}");
+                    RegistryAuth::Bearer(access_token)
+                }
+                Err(e) => {
+                    log::warn!(
+                        "Failed to exchange docker identity token: {e:#}. Using anonymous auth"
+                    );
+                    RegistryAuth::Anonymous
+                }
+            }
         }
     }
 }
 
-async fn setup_trust_repository(cli: &Cli) -> Result<Box<dyn TrustRoot>, anyhow::Error> {
+/// The trust root for keyless signatures, set up on first use and shared by
+/// every plugin loaded in a run.
+pub type TrustRootCell = OnceCell<SigstoreTrust>;
+
+/// A Rekor key and the time range it signed log entries in, as Unix seconds.
+/// Keys that were rotated out stay around, as entries logged while they were
+/// in use still verify with them.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct RekorKey {
+    pub(crate) key: Vec<u8>,
+    pub(crate) valid_from: Option<i64>,
+    pub(crate) valid_until: Option<i64>,
+}
+
+impl RekorKey {
+    /// The log id of the Rekor instance with this key, the hex SHA-256 of the
+    /// key's DER encoding.
+    pub(crate) fn log_id(&self) -> String {
+        hex::encode(Sha256::digest(&self.key))
+    }
+
+    pub(crate) fn is_valid_at(&self, time: i64) -> bool {
+        self.valid_from.is_none_or(|start| start <= time)
+            && self.valid_until.is_none_or(|end| time <= end)
+    }
+}
+
+/// A trust root along with the validity of its Rekor keys, which
+/// [`TrustRoot`] doesn't carry. As a [`TrustRoot`] it only offers the keys
+/// valid now, since the sigstore clients use the first one for every entry.
+pub struct SigstoreTrust {
+    trust_root: Box<dyn TrustRoot + Send + Sync>,
+    rekor_keys: Vec<RekorKey>,
+}
+
+impl SigstoreTrust {
+    /// A trust root whose Rekor keys have no known validity range.
+    pub(crate) fn new(trust_root: impl TrustRoot + Send + Sync + 'static) -> Self {
+        let rekor_keys = trust_root
+            .rekor_keys()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|key| RekorKey {
+                key: key.to_vec(),
+                valid_from: None,
+                valid_until: None,
+            })
+            .collect();
+        Self {
+            trust_root: Box::new(trust_root),
+            rekor_keys,
+        }
+    }
+
+    /// Every Rekor key, including the ones no longer in use.
+    pub(crate) fn all_rekor_keys(&self) -> &[RekorKey] {
+        &self.rekor_keys
+    }
+}
+
+impl TrustRoot for SigstoreTrust {
+    fn fulcio_certs(&self) -> sigstore::errors::Result<Vec<rustls_pki_types::CertificateDer<'_>>> {
+        self.trust_root.fulcio_certs()
+    }
+
+    fn rekor_keys(&self) -> sigstore::errors::Result<Vec<&[u8]>> {
+        self.trust_root.rekor_keys()
+    }
+
+    fn ctfe_keys(&self) -> sigstore::errors::Result<Vec<&[u8]>> {
+        self.trust_root.ctfe_keys()
+    }
+}
+
+/// Where the Sigstore trust root fetched through TUF is kept between runs.
+fn trust_root_cache_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("hyper-mcp").join("sigstore"))
+}
+
+pub(crate) async fn setup_trust_repository(cli: &Cli) -> Result<SigstoreTrust, anyhow::Error> {
+    if let Some(trust_root_path) = &cli.sigstore_trust_root {
+        log::info!(
+            "Using Sigstore trust root from {}",
+            trust_root_path.display()
+        );
+        return read_trusted_root(trust_root_path);
+    }
+
     if cli.use_sigstore_tuf_data {
         // Use Sigstore TUF data from the official repository
         log::info!("Using Sigstore TUF data for verification");
-        match SigstoreTrustRoot::new(None).await {
-            Ok(repo) => return Ok(Box::new(repo)),
+        let cache_dir = trust_root_cache_dir().filter(|dir| fs::create_dir_all(dir).is_ok());
+        match SigstoreTrustRoot::new(cache_dir.as_deref()).await {
+            Ok(repo) => return Ok(SigstoreTrust::new(repo)),
             Err(e) => {
                 log::error!("Failed to initialize TUF trust repository: {e}");
+                // The trust root an earlier run fetched still works offline.
+                let cached = cache_dir
+                    .map(|dir| dir.join("trusted_root.json"))
+                    .filter(|path| path.exists());
+                if let Some(cached) = cached {
+                    log::warn!("Using the cached Sigstore trust root {}", cached.display());
+                    return read_trusted_root(&cached);
+                }
                 if !cli.insecure_skip_signature {
                     return Err(anyhow!(
                         "Failed to initialize TUF trust repository and signature verification is required"
@@ -95,7 +446,11 @@ async fn setup_trust_repository(cli: &Cli) -> Result<Box<dyn TrustRoot>, anyhow:
         }
     }
 
-    // Create a manual trust repository
+    Ok(SigstoreTrust::new(manual_trust_root(cli)))
+}
+
+/// A trust root of the `--rekor-pub-keys` and `--fulcio-certs` files.
+fn manual_trust_root(cli: &Cli) -> ManualTrustRoot<'static> {
     let mut data = ManualTrustRoot::default();
 
     // Add Rekor public keys if provided
@@ -138,21 +493,466 @@ async fn setup_trust_repository(cli: &Cli) -> Result<Box<dyn TrustRoot>, anyhow:
         }
     }
 
-    Ok(Box::new(data))
+    data
+}
+
+/// Reads a Sigstore `trusted_root.json`. Rekor keys are kept with their
+/// validity range, and CT log keys when they are valid now. Fulcio
+/// certificates are kept even when expired, they may have been valid when a
+/// plugin was signed.
+fn read_trusted_root(path: &Path) -> Result<SigstoreTrust, anyhow::Error> {
+    let content = fs::read(path)
+        .with_context(|| format!("Failed to read Sigstore trust root {}", path.display()))?;
+    let trusted_root: TrustedRoot = serde_json::from_slice(&content)
+        .with_context(|| format!("Invalid Sigstore trust root {}", path.display()))?;
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as i64);
+    let is_valid = |range: Option<&TimeRange>, allow_expired: bool| {
+        let start = range.and_then(|range| range.start.as_ref());
+        let end = range.and_then(|range| range.end.as_ref());
+        start.is_none_or(|start| start.seconds <= now)
+            && (allow_expired || end.is_none_or(|end| now <= end.seconds))
+    };
+    let log_keys = |logs: &[TransparencyLogInstance]| {
+        logs.iter()
+            .filter_map(|log| log.public_key.as_ref())
+            .filter(|key| is_valid(key.valid_for.as_ref(), false))
+            .filter_map(|key| key.raw_bytes.clone())
+            .collect()
+    };
+
+    let fulcio_certs = trusted_root
+        .certificate_authorities
+        .iter()
+        .filter(|authority| is_valid(authority.valid_for.as_ref(), true))
+        .flat_map(|authority| authority.cert_chain.iter())
+        .flat_map(|chain| chain.certificates.iter())
+        .map(|certificate| {
+            sigstore::registry::Certificate {
+                encoding: sigstore::registry::CertificateEncoding::Der,
+                data: certificate.raw_bytes.clone(),
+            }
+            .try_into()
+        })
+        .collect::<Result<_, _>>()?;
+
+    let rekor_keys: Vec<RekorKey> = trusted_root
+        .tlogs
+        .iter()
+        .filter_map(|log| log.public_key.as_ref())
+        .filter_map(|key| {
+            let range = key.valid_for.as_ref();
+            Some(RekorKey {
+                key: key.raw_bytes.clone()?,
+                valid_from: range.and_then(|range| range.start.as_ref()?.seconds.into()),
+                valid_until: range.and_then(|range| range.end.as_ref()?.seconds.into()),
+            })
+        })
+        .collect();
+
+    Ok(SigstoreTrust {
+        trust_root: Box::new(ManualTrustRoot {
+            fulcio_certs,
+            rekor_keys: rekor_keys
+                .iter()
+                .filter(|key| key.is_valid_at(now))
+                .map(|key| key.key.clone())
+                .collect(),
+            ctfe_keys: log_keys(&trusted_root.ctlogs),
+        }),
+        rekor_keys,
+    })
+}
+
+/// Accepts signatures whose certificate matches any of the configured
+/// signing identities.
+#[derive(Debug)]
+pub(crate) struct IdentityVerifier {
+    identities: Vec<(Option<Regex>, Regex)>,
+}
+
+impl IdentityVerifier {
+    pub(crate) fn new(identities: &[SigningIdentity]) -> Result<Self, anyhow::Error> {
+        // The whole value has to match, not just a part of it.
+        let anchored = |pattern: &str| {
+            Regex::new(&format!("^(?:{pattern})$"))
+                .with_context(|| format!("Invalid signing identity pattern {pattern}"))
+        };
+        let identities = identities
+            .iter()
+            .map(|identity| {
+                Ok((
+                    identity.issuer.as_deref().map(anchored).transpose()?,
+                    anchored(&identity.subject)?,
+                ))
+            })
+            .collect::<Result<_, anyhow::Error>>()?;
+        Ok(Self { identities })
+    }
+
+    fn matches(&self, subject: &str, issuer: Option<&str>) -> bool {
+        self.identities
+            .iter()
+            .any(|(expected_issuer, expected_subject)| {
+                expected_subject.is_match(subject)
+                    && expected_issuer.as_ref().is_none_or(|expected| {
+                        issuer.is_some_and(|issuer| expected.is_match(issuer))
+                    })
+            })
+    }
+}
+
+/// The Fulcio certificate extension holding the OIDC issuer.
+const OIDC_ISSUER_OID: &str = "1.3.6.1.4.1.57264.1.1";
+
+impl VerificationPolicy for IdentityVerifier {
+    fn verify(&self, cert: &x509_cert::Certificate) -> PolicyResult {
+        let subject = match CertificateSubject::from_certificate(cert) {
+            Ok(CertificateSubject::Email(subject) | CertificateSubject::Uri(subject)) => subject,
+            Err(_) => return Err(PolicyError::ExtensionNotFound),
+        };
+        let issuer = cert
+            .tbs_certificate
+            .extensions
+            .iter()
+            .flatten()
+            .find(|extension| extension.extn_id.to_string() == OIDC_ISSUER_OID)
+            .and_then(|extension| std::str::from_utf8(extension.extn_value.as_bytes()).ok());
+        if self.matches(&subject, issuer) {
+            Ok(())
+        } else {
+            Err(PolicyError::ExtensionCheckFailed {
+                extension: "signing identity".to_string(),
+                expected: "one of the configured identities".to_string(),
+                actual: subject,
+            })
+        }
+    }
+}
+
+impl VerificationConstraint for IdentityVerifier {
+    fn verify(&self, signature_layer: &SignatureLayer) -> sigstore::errors::Result<bool> {
+        Ok(signature_layer
+            .certificate_signature
+            .as_ref()
+            .is_some_and(|signature| {
+                let subject = match &signature.subject {
+                    CertificateSubject::Email(email) => email,
+                    CertificateSubject::Uri(uri) => uri,
+                };
+                self.matches(subject, signature.issuer.as_deref())
+            }))
+    }
+}
+
+/// Accepts signatures made with any of the configured public keys.
+#[derive(Debug)]
+pub(crate) struct PublicKeysVerifier {
+    keys: Vec<(PublicKeyVerifier, CosignVerificationKey)>,
+}
+
+impl PublicKeysVerifier {
+    pub(crate) fn new(paths: &[PathBuf]) -> Result<Self, anyhow::Error> {
+        let keys = paths
+            .iter()
+            .map(|path| {
+                let pem = fs::read(path)
+                    .with_context(|| format!("Failed to read public key {}", path.display()))?;
+                let invalid = || format!("Invalid public key {}", path.display());
+                Ok((
+                    PublicKeyVerifier::try_from(&pem).with_context(invalid)?,
+                    CosignVerificationKey::try_from_pem(&pem).with_context(invalid)?,
+                ))
+            })
+            .collect::<Result<_, anyhow::Error>>()?;
+        Ok(Self { keys })
+    }
+
+    /// Whether the raw `signature` of `blob` was made with any of the keys.
+    pub(crate) fn verify_signature(&self, blob: &[u8], signature: &[u8]) -> bool {
+        self.keys.iter().any(|(_, key)| {
+            key.verify_signature(Signature::Raw(signature), blob)
+                .is_ok()
+        })
+    }
+
+    /// Whether the bundle holds a signature of `blob` by any of the keys.
+    fn verify_bundle(&self, blob: &[u8], bundle: &Bundle) -> bool {
+        let Some(Content::MessageSignature(signature)) = &bundle.content else {
+            return false;
+        };
+        self.verify_signature(blob, &signature.signature)
+    }
+}
+
+impl VerificationConstraint for PublicKeysVerifier {
+    fn verify(&self, signature_layer: &SignatureLayer) -> sigstore::errors::Result<bool> {
+        for (key, _) in &self.keys {
+            if key.verify(signature_layer)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+/// Who has to have signed a plugin. A verification block's public keys or
+/// identities take precedence, then `--cosign-key`, then the certificate
+/// flags.
+pub(crate) enum Signers {
+    PublicKeys(PublicKeysVerifier),
+    Identities(IdentityVerifier),
+    CertificateFlags,
 }
 
-async fn verify_image_signature(cli: &Cli, image_reference: &str) -> Result<bool, anyhow::Error> {
+impl Signers {
+    pub(crate) fn new(
+        cli: &Cli,
+        verification: Option<&VerificationConfig>,
+    ) -> Result<Self, anyhow::Error> {
+        let public_keys = verification.and_then(|v| v.public_keys.as_ref());
+        let identities = verification.and_then(|v| v.identities.as_ref());
+        Ok(match (public_keys, identities) {
+            (Some(public_keys), _) => Self::PublicKeys(PublicKeysVerifier::new(public_keys)?),
+            (None, Some(identities)) => Self::Identities(IdentityVerifier::new(identities)?),
+            (None, None) if !cli.cosign_keys.is_empty() => {
+                Self::PublicKeys(PublicKeysVerifier::new(&cli.cosign_keys)?)
+            }
+            (None, None) => Self::CertificateFlags,
+        })
+    }
+}
+
+/// The constraints an image signature has to satisfy, and whether it is
+/// keyless.
+fn verification_constraints(
+    cli: &Cli,
+    verification: Option<&VerificationConfig>,
+) -> Result<(VerificationConstraintVec, bool), anyhow::Error> {
+    Ok(match Signers::new(cli, verification)? {
+        Signers::PublicKeys(public_keys) => (vec![Box::new(public_keys)], false),
+        Signers::Identities(identities) => (vec![Box::new(identities)], true),
+        Signers::CertificateFlags => (cli_constraints(cli), true),
+    })
+}
+
+/// Whether to skip signature verification: the plugin's or image prefix's
+/// `verification.skip`, else `--insecure-skip-signature`.
+fn skip_verification(cli: &Cli, verification: Option<&VerificationConfig>) -> bool {
+    verification
+        .and_then(|verification| verification.skip)
+        .unwrap_or(cli.insecure_skip_signature)
+}
+
+/// Signing identity constraints from `--cert-email`, `--cert-url` and
+/// `--cert-issuer`.
+fn cli_constraints(cli: &Cli) -> VerificationConstraintVec {
+    let mut verification_constraints: VerificationConstraintVec = Vec::new();
+
+    if let Some(cert_email) = &cli.cert_email {
+        let issuer = cli
+            .cert_issuer
+            .as_ref()
+            .map(|i| StringVerifier::ExactMatch(i.to_string()));
+
+        verification_constraints.push(Box::new(CertSubjectEmailVerifier {
+            email: StringVerifier::ExactMatch(cert_email.to_string()),
+            issuer,
+        }));
+    }
+
+    if let Some(cert_url) = &cli.cert_url {
+        match cli.cert_issuer.as_ref() {
+            Some(issuer) => {
+                verification_constraints.push(Box::new(CertSubjectUrlVerifier {
+                    url: cert_url.to_string(),
+                    issuer: issuer.to_string(),
+                }));
+            }
+            None => {
+                log::warn!("'cert-issuer' is required when 'cert-url' is specified");
+            }
+        }
+    }
+
+    verification_constraints
+}
+
+/// `--cert-email`, `--cert-url` and `--cert-issuer` as signing identities for
+/// checking bundles. Without any of them every signer is accepted, like for
+/// image signatures.
+pub(crate) fn cli_identities(cli: &Cli) -> Vec<SigningIdentity> {
+    let issuer = cli.cert_issuer.as_deref().map(regex::escape);
+    let mut identities: Vec<_> = cli
+        .cert_email
+        .iter()
+        .map(|email| SigningIdentity {
+            issuer: issuer.clone(),
+            subject: regex::escape(email),
+        })
+        .collect();
+    if let (Some(cert_url), Some(_)) = (&cli.cert_url, &issuer) {
+        identities.push(SigningIdentity {
+            issuer: issuer.clone(),
+            subject: regex::escape(cert_url),
+        });
+    }
+    if identities.is_empty() {
+        identities.push(SigningIdentity {
+            issuer: None,
+            subject: ".*".to_string(),
+        });
+    }
+    identities
+}
+
+/// Raw wasm, as pushed by `oras push` or wasm-to-oci.
+const WASM_MEDIA_TYPE: &str = "application/wasm";
+const IMAGE_LAYER_ZSTD_MEDIA_TYPE: &str = "application/vnd.oci.image.layer.v1.tar+zstd";
+
+/// Layer media types a plugin can be pulled from: raw wasm, or a tar
+/// archive, plain or compressed, holding a `plugin.wasm`.
+const PLUGIN_LAYER_MEDIA_TYPES: [&str; 7] = [
+    WASM_MEDIA_TYPE,
+    manifest::WASM_LAYER_MEDIA_TYPE,
+    manifest::IMAGE_LAYER_MEDIA_TYPE,
+    manifest::IMAGE_DOCKER_LAYER_TAR_MEDIA_TYPE,
+    manifest::IMAGE_LAYER_GZIP_MEDIA_TYPE,
+    manifest::IMAGE_DOCKER_LAYER_GZIP_MEDIA_TYPE,
+    IMAGE_LAYER_ZSTD_MEDIA_TYPE,
+];
+
+/// The plugin in a layer of one of the `PLUGIN_LAYER_MEDIA_TYPES`, if any.
+fn plugin_from_layer(media_type: &str, data: &[u8]) -> Result<Option<Vec<u8>>, anyhow::Error> {
+    let archive: Box<dyn Read + '_> = match media_type {
+        WASM_MEDIA_TYPE | manifest::WASM_LAYER_MEDIA_TYPE => return Ok(Some(data.to_vec())),
+        manifest::IMAGE_LAYER_MEDIA_TYPE | manifest::IMAGE_DOCKER_LAYER_TAR_MEDIA_TYPE => {
+            Box::new(data)
+        }
+        manifest::IMAGE_LAYER_GZIP_MEDIA_TYPE | manifest::IMAGE_DOCKER_LAYER_GZIP_MEDIA_TYPE => {
+            Box::new(GzDecoder::new(data))
+        }
+        IMAGE_LAYER_ZSTD_MEDIA_TYPE => Box::new(zstd::Decoder::new(data)?),
+        _ => return Ok(None),
+    };
+
+    for entry_result in Archive::new(archive).entries()? {
+        match entry_result {
+            Ok(mut entry) => {
+                if let Ok(path) = entry.path() {
+                    if path.to_string_lossy().ends_with("plugin.wasm") {
+                        let mut content = Vec::new();
+                        entry.read_to_end(&mut content)?;
+                        return Ok(Some(content));
+                    }
+                }
+            }
+            Err(e) => log::info!("Error during extraction: {e}"),
+        }
+    }
+    Ok(None)
+}
+
+/// Checks a cached plugin against the Sigstore bundle stored next to it, as
+/// written by `cosign sign-blob --new-bundle-format --bundle`. Neither the
+/// registry nor Rekor are contacted, so this works offline.
+async fn verify_bundle(
+    cli: &Cli,
+    trust_root: &TrustRootCell,
+    verification: Option<&VerificationConfig>,
+    wasm_path: &Path,
+    bundle_path: &Path,
+) -> Result<(), anyhow::Error> {
+    let wasm =
+        fs::read(wasm_path).with_context(|| format!("Failed to read {}", wasm_path.display()))?;
+    let bundle = fs::read(bundle_path)
+        .with_context(|| format!("Failed to read bundle {}", bundle_path.display()))?;
+    let mut bundle: Bundle = serde_json::from_slice(&bundle)
+        .with_context(|| format!("Invalid bundle {}", bundle_path.display()))?;
+    // sigstore only knows the old spelling of the media type. v0.3 bundles
+    // carry a single certificate instead of a chain, which it also handles.
+    if let "application/vnd.dev.sigstore.bundle.v0.2+json"
+    | "application/vnd.dev.sigstore.bundle.v0.3+json" = bundle.media_type.as_str()
+    {
+        bundle.media_type = "application/vnd.dev.sigstore.bundle+json;version=0.2".to_string();
+    }
+
+    let identities = match Signers::new(cli, verification)? {
+        Signers::PublicKeys(public_keys) => {
+            return if public_keys.verify_bundle(&wasm, &bundle) {
+                Ok(())
+            } else {
+                Err(anyhow!("No signature by the configured public keys"))
+            };
+        }
+        Signers::Identities(identities) => identities,
+        Signers::CertificateFlags => IdentityVerifier::new(&cli_identities(cli))?,
+    };
+
+    let trust_root = trust_root
+        .get_or_try_init(|| setup_trust_repository(cli))
+        .await?;
+    let trust_root = ManualTrustRoot {
+        fulcio_certs: trust_root.fulcio_certs()?,
+        rekor_keys: trust_root
+            .rekor_keys()?
+            .into_iter()
+            .map(<[u8]>::to_vec)
+            .collect(),
+        ctfe_keys: trust_root
+            .ctfe_keys()?
+            .into_iter()
+            .map(<[u8]>::to_vec)
+            .collect(),
+    };
+    Verifier::new(Default::default(), trust_root)?
+        .verify(wasm.as_slice(), bundle, &identities, true)
+        .await?;
+    Ok(())
+}
+
+/// The pull credentials in the form the sigstore client takes, so signatures
+/// in private registries can be looked up.
+fn sigstore_auth(auth: &RegistryAuth) -> Auth {
+    match auth {
+        RegistryAuth::Anonymous => Auth::Anonymous,
+        RegistryAuth::Basic(username, password) => Auth::Basic(username.clone(), password.clone()),
+        RegistryAuth::Bearer(token) => Auth::Bearer(token.clone()),
+    }
+}
+
+async fn verify_image_signature(
+    cli: &Cli,
+    trust_root: &TrustRootCell,
+    image_reference: &str,
+    verification: Option<&VerificationConfig>,
+    auth: &Auth,
+    oci_config: RegistryClientConfig,
+) -> Result<bool, anyhow::Error> {
     log::info!("Verifying signature for {image_reference}");
 
-    // Set up the trust repository based on CLI arguments
-    let repo = setup_trust_repository(cli).await?;
-    let auth = &Auth::Anonymous;
+    let (verification_constraints, keyless) = verification_constraints(cli, verification)?;
+
+    // Set up the trust repository based on CLI arguments. Key-based
+    // signatures don't need Fulcio, so there's no need to fetch the TUF data
+    // for them.
+    let manual_repo;
+    let repo: &(dyn TrustRoot + Send + Sync) = if keyless {
+        trust_root
+            .get_or_try_init(|| setup_trust_repository(cli))
+            .await?
+    } else {
+        manual_repo = manual_trust_root(cli);
+        &manual_repo
+    };
 
     // Create a client builder
-    let client_builder = ClientBuilder::default();
+    let client_builder = ClientBuilder::default().with_oci_client_config(oci_config);
 
     // Create client with trust repository
-    let client_builder = match client_builder.with_trust_repository(repo.as_ref()) {
+    let client_builder = match client_builder.with_trust_repository(repo) {
         Ok(builder) => builder,
         Err(e) => return Err(anyhow!("Failed to set up trust repository: {}", e)),
     };
@@ -196,35 +996,6 @@ async fn verify_image_signature(cli: &Cli, image_reference: &str) -> Result<bool
         return Ok(false);
     }
 
-    // Build verification constraints based on CLI options
-    let mut verification_constraints: VerificationConstraintVec = Vec::new();
-
-    if let Some(cert_email) = &cli.cert_email {
-        let issuer = cli
-            .cert_issuer
-            .as_ref()
-            .map(|i| StringVerifier::ExactMatch(i.to_string()));
-
-        verification_constraints.push(Box::new(CertSubjectEmailVerifier {
-            email: StringVerifier::ExactMatch(cert_email.to_string()),
-            issuer,
-        }));
-    }
-
-    if let Some(cert_url) = &cli.cert_url {
-        match cli.cert_issuer.as_ref() {
-            Some(issuer) => {
-                verification_constraints.push(Box::new(CertSubjectUrlVerifier {
-                    url: cert_url.to_string(),
-                    issuer: issuer.to_string(),
-                }));
-            }
-            None => {
-                log::warn!("'cert-issuer' is required when 'cert-url' is specified");
-            }
-        }
-    }
-
     // Verify the constraints
     match verify_constraints(&signature_layers, verification_constraints.iter()) {
         Ok(()) => {
@@ -242,14 +1013,102 @@ async fn verify_image_signature(cli: &Cli, image_reference: &str) -> Result<bool
     }
 }
 
+/// Where the annotations of the manifest a plugin was pulled from are kept,
+/// next to the plugin.
+fn annotations_path(local_output_path: &str) -> PathBuf {
+    PathBuf::from(format!("{local_output_path}.annotations.json"))
+}
+
+/// Where the verification settings a cached plugin was checked against are
+/// kept.
+fn verified_settings_path(local_output_path: &str) -> PathBuf {
+    PathBuf::from(format!("{local_output_path}.verification.json"))
+}
+
+/// Everything that decides how a plugin is verified: its verification block
+/// and the command line flags that fill in what the block leaves unset.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+struct VerifiedSettings {
+    verification: Option<VerificationConfig>,
+    insecure_skip_signature: bool,
+    cosign_keys: Vec<PathBuf>,
+    cert_issuer: Option<String>,
+    cert_email: Option<String>,
+    cert_url: Option<String>,
+}
+
+impl VerifiedSettings {
+    fn new(cli: &Cli, verification: Option<&VerificationConfig>) -> Self {
+        Self {
+            verification: verification.cloned(),
+            insecure_skip_signature: cli.insecure_skip_signature,
+            cosign_keys: cli.cosign_keys.clone(),
+            cert_issuer: cli.cert_issuer.clone(),
+            cert_email: cli.cert_email.clone(),
+            cert_url: cli.cert_url.clone(),
+        }
+    }
+
+    /// The settings the plugin cached at `local_output_path` was verified
+    /// against, if any.
+    fn read(local_output_path: &str) -> Option<Self> {
+        let json = fs::read(verified_settings_path(local_output_path)).ok()?;
+        serde_json::from_slice(&json).ok()
+    }
+}
+
+/// The annotations of the manifest the plugin cached at `local_output_path`
+/// was pulled from.
+pub fn cached_annotations(local_output_path: &str) -> anyhow::Result<BTreeMap<String, String>> {
+    let path = annotations_path(local_output_path);
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let json = fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_slice(&json).with_context(|| format!("Invalid {}", path.display()))
+}
+
 pub async fn pull_and_extract_oci_image(
     cli: &Cli,
-    client: &Client,
+    registry: &RegistryClient,
+    trust_root: &TrustRootCell,
+    auths: &Option<HashMap<Url, AuthConfig>>,
+    verification: Option<&VerificationConfig>,
     image_reference: &str,
-    target_file_path: &str,
     local_output_path: &str,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    if Path::new(local_output_path).exists() {
+    let skip = skip_verification(cli, verification);
+    let settings = VerifiedSettings::new(cli, verification);
+    let bundle_path = PathBuf::from(format!("{local_output_path}.sigstore.json"));
+    // A cached plugin only counts when it was verified against the current
+    // settings, or when its signature can be checked against a bundle next to
+    // it and no attestations are required.
+    let has_attestation_policy = verification.is_some_and(|v| v.attestations.is_some());
+    let cached = Path::new(local_output_path).exists()
+        && (skip
+            || VerifiedSettings::read(local_output_path).as_ref() == Some(&settings)
+            || (bundle_path.exists() && !has_attestation_policy));
+    if !cached && Path::new(local_output_path).exists() {
+        log::info!(
+            "{local_output_path} wasn't verified against the current verification settings, pulling {image_reference} again"
+        );
+    }
+    if cached {
+        if bundle_path.exists() && !skip {
+            verify_bundle(
+                cli,
+                trust_root,
+                verification,
+                Path::new(local_output_path),
+                &bundle_path,
+            )
+            .await
+            .map_err(|e| format!("Bundle verification failed for {local_output_path}: {e:#}"))?;
+            log::info!(
+                "Verified {local_output_path} against its bundle {}",
+                bundle_path.display()
+            );
+        }
         log::info!(
             "Plugin {image_reference} already cached at: {local_output_path}. Skipping downloading."
         );
@@ -259,12 +1118,22 @@ pub async fn pull_and_extract_oci_image(
     log::info!("Pulling {image_reference} ...");
 
     let reference = Reference::try_from(image_reference)?;
-    let auth = build_auth(&reference);
+    let auth = build_auth(registry, &reference, auths, "pull").await;
+    let client = &registry.client;
 
     // Verify the image signature if it's an OCI image and verification is enabled
-    if !cli.insecure_skip_signature {
+    if !skip {
         log::info!("Signature verification enabled for {image_reference}");
-        match verify_image_signature(cli, image_reference).await {
+        match verify_image_signature(
+            cli,
+            trust_root,
+            image_reference,
+            verification,
+            &sigstore_auth(&auth),
+            registry.config.clone(),
+        )
+        .await
+        {
             Ok(verified) => {
                 if !verified {
                     return Err(format!(
@@ -277,57 +1146,999 @@ pub async fn pull_and_extract_oci_image(
                 return Err(format!("Image signature verification failed: {e}").into());
             }
         }
+        if let Some(verification) = verification {
+            verify_attestations(cli, client, trust_root, &reference, &auth, verification)
+                .await
+                .map_err(|e| format!("Attestation verification failed: {e:#}"))?;
+        }
     } else {
         log::warn!("Signature verification disabled for {image_reference}");
     }
 
-    // Accept both OCI and Docker manifest types
-    let manifest = client
-        .pull(
-            &reference,
+    let (manifest, _) = client.pull_image_manifest(&reference, &auth).await?;
+    for layer in &manifest.layers {
+        if !PLUGIN_LAYER_MEDIA_TYPES.contains(&layer.media_type.as_str()) {
+            log::debug!("Skipping {} layer {}", layer.media_type, layer.digest);
+            continue;
+        }
+        let mut buf = Vec::new();
+        client.pull_blob(&reference, layer, &mut buf).await?;
+
+        if let Some(content) = plugin_from_layer(&layer.media_type, &buf)? {
+            if let Some(parent) = Path::new(local_output_path).parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(local_output_path, content)?;
+            fs::write(
+                verified_settings_path(local_output_path),
+                serde_json::to_vec(&settings)?,
+            )?;
+            let annotations_path = annotations_path(local_output_path);
+            match &manifest.annotations {
+                Some(annotations) if !annotations.is_empty() => {
+                    fs::write(&annotations_path, serde_json::to_vec(annotations)?)?
+                }
+                _ if annotations_path.exists() => fs::remove_file(&annotations_path)?,
+                _ => {}
+            }
+            log::info!("Successfully extracted to: {local_output_path}");
+            return Ok(());
+        }
+    }
+
+    let media_types: Vec<_> = manifest
+        .layers
+        .iter()
+        .map(|layer| layer.media_type.as_str())
+        .collect();
+    Err(format!(
+        "No wasm layer or plugin.wasm found in {image_reference}, its layers are {media_types:?}"
+    )
+    .into())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::config::AttestationPolicy;
+    use crate::test_registry::TestRegistry;
+    use axum::{
+        Form, Json, Router,
+        http::{HeaderMap, StatusCode, header},
+        routing::{get, post},
+    };
+    use clap::Parser;
+
+    fn auths(entries: &[(&str, &str)]) -> HashMap<Url, AuthConfig> {
+        entries
+            .iter()
+            .map(|(url, token)| {
+                (
+                    Url::parse(url).unwrap(),
+                    AuthConfig::Token {
+                        token: token.to_string(),
+                    },
+                )
+            })
+            .collect()
+    }
+
+    fn token_of<'a>(auth: Option<(&Url, &'a AuthConfig)>) -> Option<&'a str> {
+        match auth {
+            Some((_, AuthConfig::Token { token })) => Some(token),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_find_registry_auth() {
+        let auths = auths(&[
+            ("https://ghcr.io", "ghcr"),
+            ("oci://ghcr.io/myorg", "myorg"),
+            ("https://localhost:5000", "local"),
+            ("https://docker.io", "docker"),
+            ("https://ghcr.io.evil.com", "evil"),
+        ]);
+        let find = |image: &str| {
+            token_of(find_registry_auth(
+                &auths,
+                &Reference::try_from(image).unwrap(),
+            ))
+        };
+
+        assert_eq!(find("ghcr.io/tuananh/time-plugin:latest"), Some("ghcr"));
+        assert_eq!(find("ghcr.io/myorg/plugin:latest"), Some("myorg"));
+        assert_eq!(find("ghcr.io/myorg-other/plugin:latest"), Some("ghcr"));
+        assert_eq!(find("localhost:5000/plugin:latest"), Some("local"));
+        assert_eq!(find("docker.io/library/plugin:latest"), Some("docker"));
+        assert_eq!(find("localhost:5001/plugin:latest"), None);
+        assert_eq!(find("quay.io/org/plugin:latest"), None);
+    }
+
+    #[test]
+    fn test_identity_verifier_matches() {
+        let identities = [
+            SigningIdentity {
+                issuer: Some("https://token.actions.githubusercontent.com".to_string()),
+                subject: "https://github.com/tuananh/hyper-mcp/.github/workflows/.+".to_string(),
+            },
+            SigningIdentity {
+                issuer: None,
+                subject: r".+@example\.com".to_string(),
+            },
+        ];
+        let verifier = IdentityVerifier::new(&identities).unwrap();
+        let github_issuer = Some("https://token.actions.githubusercontent.com");
+
+        assert!(verifier.matches(
+            "https://github.com/tuananh/hyper-mcp/.github/workflows/release.yml@refs/tags/v1",
+            github_issuer
+        ));
+        assert!(!verifier.matches(
+            "https://github.com/tuananh/hyper-mcp/.github/workflows/release.yml",
+            Some("https://accounts.google.com")
+        ));
+        assert!(!verifier.matches(
+            "https://github.com/evil/hyper-mcp/.github/workflows/release.yml",
+            github_issuer
+        ));
+        assert!(verifier.matches("dev@example.com", None));
+        // Patterns have to match the whole subject.
+        assert!(!verifier.matches("dev@example.com.evil.org", None));
+
+        assert!(
+            IdentityVerifier::new(&[SigningIdentity {
+                issuer: None,
+                subject: "(unclosed".to_string(),
+            }])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_skip_verification() {
+        let mut cli = Cli::parse_from(["hyper-mcp"]);
+        let skip = |skip| VerificationConfig {
+            skip,
+            ..Default::default()
+        };
+
+        assert!(!skip_verification(&cli, None));
+        assert!(skip_verification(&cli, Some(&skip(Some(true)))));
+        cli.insecure_skip_signature = true;
+        assert!(skip_verification(&cli, None));
+        assert!(skip_verification(&cli, Some(&skip(None))));
+        assert!(!skip_verification(&cli, Some(&skip(Some(false)))));
+    }
+
+    #[test]
+    fn test_parse_bearer_challenge() {
+        assert_eq!(
+            parse_bearer_challenge(
+                r#"Bearer realm="https://auth.example.com/token",service="registry.example.com",scope="repository:org/plugin:pull""#
+            ),
+            Some((
+                "https://auth.example.com/token".to_string(),
+                Some("registry.example.com".to_string())
+            ))
+        );
+        assert_eq!(
+            parse_bearer_challenge(r#"bearer realm="https://example.com/oauth2/token""#),
+            Some(("https://example.com/oauth2/token".to_string(), None))
+        );
+        assert_eq!(parse_bearer_challenge(r#"Basic realm="registry""#), None);
+        assert_eq!(parse_bearer_challenge("Bearer service=\"x\""), None);
+    }
+
+    /// A registry whose `/v2/` asks for a bearer token from its own `/token`,
+    /// which only accepts the refresh token `identity-token`.
+    async fn start_token_service() -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let realm = format!("{base_url}/token");
+        let app = Router::new()
+            .route(
+                "/v2/",
+                get(move || async move {
+                    let mut headers = HeaderMap::new();
+                    headers.insert(
+                        header::WWW_AUTHENTICATE,
+                        format!(r#"Bearer realm="{realm}",service="test-registry""#)
+                            .parse()
+                            .unwrap(),
+                    );
+                    (StatusCode::UNAUTHORIZED, headers)
+                }),
+            )
+            .route(
+                "/token",
+                post(|Form(form): Form<HashMap<String, String>>| async move {
+                    let expected = [
+                        ("grant_type", "refresh_token"),
+                        ("service", "test-registry"),
+                        ("scope", "repository:org/plugin:pull"),
+                        ("refresh_token", "identity-token"),
+                    ];
+                    if expected
+                        .iter()
+                        .all(|(key, value)| form.get(*key).map(String::as_str) == Some(value))
+                    {
+                        Ok(Json(serde_json::json!({ "access_token": "access-token" })))
+                    } else {
+                        Err(StatusCode::UNAUTHORIZED)
+                    }
+                }),
+            );
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        base_url
+    }
+
+    #[tokio::test]
+    async fn test_exchange_identity_token() {
+        let base_url = start_token_service().await;
+        let client = reqwest::Client::new();
+
+        let access_token =
+            exchange_identity_token(&client, &base_url, "org/plugin", "pull", "identity-token")
+                .await
+                .unwrap();
+        assert_eq!(access_token, "access-token");
+
+        let error = exchange_identity_token(&client, &base_url, "org/plugin", "pull", "wrong")
+            .await
+            .unwrap_err();
+        assert!(format!("{error:#}").contains("rejected the identity token"));
+    }
+
+    #[tokio::test]
+    async fn test_build_auth_only_exchanges_identity_tokens() {
+        let base_url = start_token_service().await;
+        let registry = RegistryClient::new(http_config()).unwrap();
+        let server = base_url.trim_start_matches("http://");
+        let reference: Reference = format!("{server}/org/plugin:1.0").parse().unwrap();
+        let auths_with = |auth: AuthConfig| {
+            Some(HashMap::from([(
+                Url::parse(&format!("https://{server}")).unwrap(),
+                auth,
+            )]))
+        };
+
+        let auths = auths_with(AuthConfig::Token {
+            token: "identity-token".to_string(),
+        });
+        assert_eq!(
+            build_auth(&registry, &reference, &auths, "pull").await,
+            RegistryAuth::Bearer("identity-token".to_string())
+        );
+
+        let auths = auths_with(AuthConfig::IdentityToken {
+            identity_token: "identity-token".to_string(),
+        });
+        assert_eq!(
+            build_auth(&registry, &reference, &auths, "pull").await,
+            RegistryAuth::Bearer("access-token".to_string())
+        );
+
+        let auths = auths_with(AuthConfig::IdentityToken {
+            identity_token: "wrong".to_string(),
+        });
+        assert_eq!(
+            build_auth(&registry, &reference, &auths, "pull").await,
+            RegistryAuth::Anonymous
+        );
+    }
+
+    /// Pushes a minimal image as `repository:latest` and returns its digest.
+    pub(crate) fn push_test_image(registry: &TestRegistry, repository: &str) -> String {
+        let config = registry.push_blob(b"{}");
+        registry.push_manifest(
+            repository,
+            "latest",
+            manifest::OCI_IMAGE_MEDIA_TYPE,
+            &serde_json::json!({
+                "schemaVersion": 2,
+                "mediaType": manifest::OCI_IMAGE_MEDIA_TYPE,
+                "config": {
+                    "mediaType": manifest::IMAGE_CONFIG_MEDIA_TYPE,
+                    "digest": config,
+                    "size": 2
+                },
+                "layers": []
+            }),
+        )
+    }
+
+    /// Signs an image like `cosign sign --key` does, storing the signature
+    /// in the registry next to the image.
+    fn sign_test_image(
+        registry: &TestRegistry,
+        repository: &str,
+        image_digest: &str,
+        signer: &sigstore::crypto::signing_key::SigStoreSigner,
+    ) {
+        use base64::Engine;
+
+        let payload = serde_json::to_vec(&serde_json::json!({
+            "critical": {
+                "identity": { "docker-reference": format!("{}/{repository}", registry.address) },
+                "image": { "docker-manifest-digest": image_digest },
+                "type": "cosign container image signature"
+            },
+            "optional": null
+        }))
+        .unwrap();
+        let signature =
+            base64::engine::general_purpose::STANDARD.encode(signer.sign(&payload).unwrap());
+        let config = registry.push_blob(b"{}");
+        let layer = registry.push_blob(&payload);
+        registry.push_manifest(
+            repository,
+            &format!("{}.sig", image_digest.replace(':', "-")),
+            manifest::OCI_IMAGE_MEDIA_TYPE,
+            &serde_json::json!({
+                "schemaVersion": 2,
+                "mediaType": manifest::OCI_IMAGE_MEDIA_TYPE,
+                "config": {
+                    "mediaType": manifest::IMAGE_CONFIG_MEDIA_TYPE,
+                    "digest": config,
+                    "size": 2
+                },
+                "layers": [{
+                    "mediaType": "application/vnd.dev.cosign.simplesigning.v1+json",
+                    "digest": layer,
+                    "size": payload.len(),
+                    "annotations": { "dev.cosignproject.cosign/signature": signature }
+                }]
+            }),
+        );
+    }
+
+    fn write_public_key(
+        path: &Path,
+        signer: &sigstore::crypto::signing_key::SigStoreSigner,
+    ) -> PathBuf {
+        let pem = signer
+            .to_sigstore_keypair()
+            .unwrap()
+            .public_key_to_pem()
+            .unwrap();
+        fs::write(path, pem).unwrap();
+        path.to_path_buf()
+    }
+
+    fn http_config() -> RegistryClientConfig {
+        RegistryClientConfig {
+            protocol: sigstore::registry::ClientProtocol::Http,
+            ..Default::default()
+        }
+    }
+
+    fn offline_cli() -> Cli {
+        let mut cli = Cli::parse_from(["hyper-mcp"]);
+        cli.use_sigstore_tuf_data = false;
+        cli
+    }
+
+    #[tokio::test]
+    async fn test_verify_image_signature_uses_registry_credentials() {
+        let registry = TestRegistry::start(Some(("admin", "secret"))).await;
+        let image_digest = push_test_image(&registry, "org/plugin");
+        let cli = offline_cli();
+        let image_reference = format!("{}/org/plugin:latest", registry.address);
+        let signature_path = format!(
+            "/v2/org/plugin/manifests/{}.sig",
+            image_digest.replace(':', "-")
+        );
+
+        // Anonymously the image can't even be resolved to its digest.
+        let verified = verify_image_signature(
+            &cli,
+            &TrustRootCell::new(),
+            &image_reference,
+            None,
+            &Auth::Anonymous,
+            http_config(),
+        )
+        .await
+        .unwrap();
+        assert!(!verified);
+        assert!(registry.requests().is_empty());
+
+        // With credentials the signature is looked up, there just is none.
+        let auth = sigstore_auth(&RegistryAuth::Basic(
+            "admin".to_string(),
+            "secret".to_string(),
+        ));
+        let verified = verify_image_signature(
+            &cli,
+            &TrustRootCell::new(),
+            &image_reference,
+            None,
             &auth,
-            vec![
-                manifest::IMAGE_MANIFEST_MEDIA_TYPE,
-                manifest::IMAGE_DOCKER_LAYER_GZIP_MEDIA_TYPE,
-                manifest::IMAGE_LAYER_GZIP_MEDIA_TYPE,
-            ],
+            http_config(),
         )
-        .await?;
+        .await
+        .unwrap();
+        assert!(!verified);
+        let requests = registry.requests();
+        assert!(
+            requests
+                .iter()
+                .any(|request| request.ends_with("/v2/org/plugin/manifests/latest")),
+            "{requests:?}"
+        );
+        assert!(
+            requests
+                .iter()
+                .any(|request| request.ends_with(&signature_path)),
+            "{requests:?}"
+        );
+    }
 
-    for layer in manifest.layers.iter() {
-        let mut buf = Vec::new();
-        let desc = OciDescriptor {
-            digest: layer.sha256_digest().clone(),
-            media_type: "application/vnd.docker.image.rootfs.diff.tar.gzip".to_string(),
+    #[tokio::test]
+    async fn test_verify_image_signature_with_public_keys() {
+        use sigstore::crypto::SigningScheme;
+
+        let registry = TestRegistry::start(None).await;
+        let image_digest = push_test_image(&registry, "org/plugin");
+        let image_reference = format!("{}/org/plugin:latest", registry.address);
+        let signer = SigningScheme::ECDSA_P256_SHA256_ASN1
+            .create_signer()
+            .unwrap();
+        sign_test_image(&registry, "org/plugin", &image_digest, &signer);
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let signing_key = write_public_key(&temp_dir.path().join("cosign.pub"), &signer);
+        let other_key = write_public_key(
+            &temp_dir.path().join("other.pub"),
+            &SigningScheme::ECDSA_P256_SHA256_ASN1
+                .create_signer()
+                .unwrap(),
+        );
+        let with_keys = |keys: &[&PathBuf]| VerificationConfig {
+            public_keys: Some(keys.iter().map(|key| key.to_path_buf()).collect()),
             ..Default::default()
         };
-        client.pull_blob(&reference, &desc, &mut buf).await.unwrap();
-
-        let gz_extract = GzDecoder::new(&buf[..]);
-        let mut archive_extract = Archive::new(gz_extract);
-
-        for entry_result in archive_extract.entries()? {
-            match entry_result {
-                Ok(mut entry) => {
-                    if let Ok(path) = entry.path() {
-                        let path_str = path.to_string_lossy();
-                        if path_str.ends_with(target_file_path) || path_str.ends_with("plugin.wasm")
-                        {
-                            if let Some(parent) = Path::new(local_output_path).parent() {
-                                fs::create_dir_all(parent)?;
+        let verify = |cli: Cli, verification: Option<VerificationConfig>| {
+            let image_reference = image_reference.clone();
+            async move {
+                verify_image_signature(
+                    &cli,
+                    &TrustRootCell::new(),
+                    &image_reference,
+                    verification.as_ref(),
+                    &Auth::Anonymous,
+                    http_config(),
+                )
+                .await
+            }
+        };
+
+        // Any of the configured keys may have signed the image.
+        assert!(
+            verify(offline_cli(), Some(with_keys(&[&other_key, &signing_key])))
+                .await
+                .unwrap()
+        );
+        assert!(
+            !verify(offline_cli(), Some(with_keys(&[&other_key])))
+                .await
+                .unwrap()
+        );
+
+        // `--cosign-key` is the default when the plugin doesn't say otherwise.
+        let mut cli = offline_cli();
+        cli.cosign_keys = vec![signing_key.clone()];
+        assert!(verify(cli, None).await.unwrap());
+
+        // Keyless identities don't accept a key-based signature.
+        let mut cli = offline_cli();
+        cli.cosign_keys = vec![signing_key.clone()];
+        let keyless = VerificationConfig {
+            identities: Some(vec![SigningIdentity {
+                issuer: None,
+                subject: ".+".to_string(),
+            }]),
+            ..Default::default()
+        };
+        assert!(!verify(cli, Some(keyless)).await.unwrap());
+
+        let missing_key = temp_dir.path().join("missing.pub");
+        let error = verify(offline_cli(), Some(with_keys(&[&missing_key])))
+            .await
+            .unwrap_err();
+        assert!(format!("{error:#}").contains("Failed to read public key"));
+    }
+
+    #[test]
+    fn test_read_trusted_root() {
+        use base64::Engine;
+
+        let encode = |data: &[u8]| base64::engine::general_purpose::STANDARD.encode(data);
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("trusted_root.json");
+        fs::write(
+            &path,
+            serde_json::to_vec(&serde_json::json!({
+                "mediaType": "application/vnd.dev.sigstore.trustedroot+json;version=0.1",
+                "tlogs": [
+                    {
+                        "baseUrl": "https://rekor.example.com",
+                        "publicKey": {
+                            "rawBytes": encode(b"current rekor key"),
+                            "validFor": { "start": "2021-01-12T11:53:27Z" }
+                        }
+                    },
+                    {
+                        "baseUrl": "https://old-rekor.example.com",
+                        "publicKey": {
+                            "rawBytes": encode(b"expired rekor key"),
+                            "validFor": {
+                                "start": "2020-01-01T00:00:00Z",
+                                "end": "2021-01-01T00:00:00Z"
                             }
-                            let mut content = Vec::new();
-                            entry.read_to_end(&mut content)?;
-                            fs::write(local_output_path, content)?;
-                            log::info!("Successfully extracted to: {local_output_path}");
-                            return Ok(());
                         }
                     }
+                ],
+                "certificateAuthorities": [{
+                    "uri": "https://fulcio.example.com",
+                    "certChain": { "certificates": [{ "rawBytes": encode(b"fulcio root") }] },
+                    "validFor": {
+                        "start": "2021-03-07T03:20:29Z",
+                        "end": "2022-12-31T23:59:59.999Z"
+                    }
+                }],
+                "ctlogs": [{
+                    "baseUrl": "https://ctfe.example.com",
+                    "publicKey": { "rawBytes": encode(b"ctfe key") }
+                }]
+            }))
+            .unwrap(),
+        )
+        .unwrap();
+
+        let trust_root = read_trusted_root(&path).unwrap();
+        // The sigstore clients only get the current Rekor key, the expired
+        // one is kept for entries logged before it was rotated out.
+        assert_eq!(trust_root.rekor_keys().unwrap(), vec![b"current rekor key"]);
+        let rekor_keys = trust_root.all_rekor_keys();
+        assert_eq!(rekor_keys.len(), 2);
+        assert_eq!(rekor_keys[1].key, b"expired rekor key");
+        assert!(rekor_keys[1].is_valid_at(1_590_000_000));
+        assert!(!rekor_keys[1].is_valid_at(1_620_000_000));
+        assert!(rekor_keys[0].is_valid_at(1_620_000_000));
+        assert!(!rekor_keys[0].is_valid_at(1_590_000_000));
+        assert_eq!(trust_root.ctfe_keys().unwrap(), vec![b"ctfe key"]);
+        // Expired Fulcio certificates may still have issued older signatures.
+        let fulcio_certs = trust_root.fulcio_certs().unwrap();
+        assert_eq!(fulcio_certs.len(), 1);
+        assert_eq!(fulcio_certs[0].as_ref(), b"fulcio root");
+
+        fs::write(&path, "not a trust root").unwrap();
+        let Err(error) = read_trusted_root(&path) else {
+            panic!("Expected an invalid trust root");
+        };
+        assert!(format!("{error:#}").contains("Invalid Sigstore trust root"));
+    }
+
+    #[tokio::test]
+    async fn test_cached_plugin_verified_against_bundle() {
+        use base64::Engine;
+        use sha2::{Digest, Sha256};
+        use sigstore::crypto::SigningScheme;
+
+        let encode = |data: &[u8]| base64::engine::general_purpose::STANDARD.encode(data);
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let wasm_path = temp_dir.path().join("time-1234567.wasm");
+        let wasm = b"\0asm plugin";
+        fs::write(&wasm_path, wasm).unwrap();
+
+        // A bundle like `cosign sign-blob --key --new-bundle-format` writes.
+        let signer = SigningScheme::ECDSA_P256_SHA256_ASN1
+            .create_signer()
+            .unwrap();
+        fs::write(
+            temp_dir.path().join("time-1234567.wasm.sigstore.json"),
+            serde_json::to_vec(&serde_json::json!({
+                "mediaType": "application/vnd.dev.sigstore.bundle.v0.3+json",
+                "verificationMaterial": { "publicKey": { "hint": "" } },
+                "messageSignature": {
+                    "messageDigest": {
+                        "algorithm": "SHA2_256",
+                        "digest": encode(&Sha256::digest(wasm))
+                    },
+                    "signature": encode(&signer.sign(wasm).unwrap())
                 }
-                Err(e) => log::info!("Error during extraction: {e}"),
+            }))
+            .unwrap(),
+        )
+        .unwrap();
+        let signing_key = write_public_key(&temp_dir.path().join("cosign.pub"), &signer);
+        let other_key = write_public_key(
+            &temp_dir.path().join("other.pub"),
+            &SigningScheme::ECDSA_P256_SHA256_ASN1
+                .create_signer()
+                .unwrap(),
+        );
+
+        // The registry is never contacted for a cached plugin.
+        let client = RegistryClient::new(Default::default()).unwrap();
+        let load = |cli: Cli, public_key: &PathBuf| {
+            let verification = VerificationConfig {
+                public_keys: Some(vec![public_key.clone()]),
+                ..Default::default()
+            };
+            let client = &client;
+            let wasm_path = wasm_path.to_str().unwrap().to_string();
+            async move {
+                pull_and_extract_oci_image(
+                    &cli,
+                    client,
+                    &TrustRootCell::new(),
+                    &None,
+                    Some(&verification),
+                    "127.0.0.1:1/org/time:latest",
+                    &wasm_path,
+                )
+                .await
+                .map_err(|e| e.to_string())
             }
+        };
+
+        load(offline_cli(), &signing_key).await.unwrap();
+        let error = load(offline_cli(), &other_key).await.unwrap_err();
+        assert!(
+            error.contains("No signature by the configured public keys"),
+            "{error}"
+        );
+
+        // A cached plugin that was changed after signing is refused.
+        fs::write(&wasm_path, b"\0asm tampered").unwrap();
+        assert!(load(offline_cli(), &signing_key).await.is_err());
+
+        // Skipping verification skips the bundle too.
+        let mut cli = offline_cli();
+        cli.insecure_skip_signature = true;
+        assert!(
+            pull_and_extract_oci_image(
+                &cli,
+                &client,
+                &TrustRootCell::new(),
+                &None,
+                None,
+                "127.0.0.1:1/org/time:latest",
+                wasm_path.to_str().unwrap(),
+            )
+            .await
+            .is_ok()
+        );
+    }
+
+    /// Pushes an image whose only layer is `layer` as `repository:latest`
+    /// and returns its digest.
+    pub(crate) fn push_plugin_image(
+        registry: &TestRegistry,
+        repository: &str,
+        media_type: &str,
+        layer: &[u8],
+    ) -> String {
+        let config = registry.push_blob(b"{}");
+        let digest = registry.push_blob(layer);
+        registry.push_manifest(
+            repository,
+            "latest",
+            manifest::OCI_IMAGE_MEDIA_TYPE,
+            &serde_json::json!({
+                "schemaVersion": 2,
+                "mediaType": manifest::OCI_IMAGE_MEDIA_TYPE,
+                "config": {
+                    "mediaType": manifest::IMAGE_CONFIG_MEDIA_TYPE,
+                    "digest": config,
+                    "size": 2
+                },
+                "layers": [{ "mediaType": media_type, "digest": digest, "size": layer.len() }]
+            }),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_pull_plugin_layers() {
+        use flate2::{Compression, write::GzEncoder};
+        use std::io::Write;
+
+        let wasm = b"\0asm plugin".to_vec();
+        let mut tar = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(wasm.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        tar.append_data(&mut header, "plugin.wasm", wasm.as_slice())
+            .unwrap();
+        let tar = tar.into_inner().unwrap();
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(&tar).unwrap();
+        let gzip = gzip.finish().unwrap();
+        let zstd = zstd::encode_all(tar.as_slice(), 0).unwrap();
+
+        let registry = TestRegistry::start(None).await;
+        let layers = [
+            ("wasm", WASM_MEDIA_TYPE, &wasm),
+            ("wasm-layer", manifest::WASM_LAYER_MEDIA_TYPE, &wasm),
+            ("tar", manifest::IMAGE_LAYER_MEDIA_TYPE, &tar),
+            (
+                "docker-tar",
+                manifest::IMAGE_DOCKER_LAYER_TAR_MEDIA_TYPE,
+                &tar,
+            ),
+            ("gzip", manifest::IMAGE_LAYER_GZIP_MEDIA_TYPE, &gzip),
+            ("zstd", IMAGE_LAYER_ZSTD_MEDIA_TYPE, &zstd),
+        ];
+        for (repository, media_type, layer) in layers {
+            push_plugin_image(&registry, repository, media_type, layer);
         }
+        push_plugin_image(&registry, "readme", "text/markdown", b"# Plugin");
+
+        let mut cli = offline_cli();
+        cli.insecure_skip_signature = true;
+        let client = RegistryClient::new(http_config()).unwrap();
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let pull = |repository: &str| {
+            let image_reference = format!("{}/{repository}:latest", registry.address);
+            let output_path = temp_dir.path().join(format!("{repository}.wasm"));
+            let (cli, client) = (&cli, &client);
+            async move {
+                pull_and_extract_oci_image(
+                    cli,
+                    client,
+                    &TrustRootCell::new(),
+                    &None,
+                    None,
+                    &image_reference,
+                    output_path.to_str().unwrap(),
+                )
+                .await
+                .map_err(|e| e.to_string())?;
+                Ok::<_, String>(fs::read(output_path).unwrap())
+            }
+        };
+
+        for (repository, _, _) in layers {
+            assert_eq!(pull(repository).await.unwrap(), wasm, "{repository}");
+        }
+        let error = pull("readme").await.unwrap_err();
+        assert!(error.contains("No wasm layer or plugin.wasm"), "{error}");
+        assert!(error.contains("text/markdown"), "{error}");
     }
 
-    Err("Target file not found in any layer".into())
+    #[tokio::test]
+    async fn test_cached_plugin_rechecked_against_attestation_policy() {
+        use sigstore::crypto::SigningScheme;
+
+        let registry = TestRegistry::start(None).await;
+        let image_digest = push_plugin_image(
+            &registry,
+            "org/plugin",
+            manifest::WASM_LAYER_MEDIA_TYPE,
+            b"\0asm plugin",
+        );
+        let signer = SigningScheme::ECDSA_P256_SHA256_ASN1
+            .create_signer()
+            .unwrap();
+        sign_test_image(&registry, "org/plugin", &image_digest, &signer);
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let public_key = write_public_key(&temp_dir.path().join("cosign.pub"), &signer);
+        let output_path = temp_dir.path().join("plugin.wasm");
+        let output_path = output_path.to_str().unwrap();
+        fs::write(output_path, b"\0asm cached").unwrap();
+
+        let client = RegistryClient::new(http_config()).unwrap();
+        let pull = |policy: AttestationPolicy| {
+            let verification = VerificationConfig {
+                public_keys: Some(vec![public_key.clone()]),
+                attestations: Some(policy),
+                ..Default::default()
+            };
+            let client = &client;
+            let image_reference = format!("{}/org/plugin:latest", registry.address);
+            async move {
+                pull_and_extract_oci_image(
+                    &offline_cli(),
+                    client,
+                    &TrustRootCell::new(),
+                    &None,
+                    Some(&verification),
+                    &image_reference,
+                    output_path,
+                )
+                .await
+                .map_err(|e| e.to_string())
+            }
+        };
+        let sbom = AttestationPolicy {
+            sbom: Some(true),
+            ..Default::default()
+        };
+
+        // Nothing records that the cached plugin's attestations were
+        // verified, so it is pulled again, and the image has none.
+        let error = pull(sbom.clone()).await.unwrap_err();
+        assert!(error.contains("Attestation verification failed"), "{error}");
+
+        // Verified against the same policy, the cache is used as is.
+        let verification = VerificationConfig {
+            public_keys: Some(vec![public_key.clone()]),
+            attestations: Some(sbom.clone()),
+            ..Default::default()
+        };
+        fs::write(
+            verified_settings_path(output_path),
+            serde_json::to_vec(&VerifiedSettings::new(&offline_cli(), Some(&verification)))
+                .unwrap(),
+        )
+        .unwrap();
+        let requests = registry.requests().len();
+        pull(sbom).await.unwrap();
+        assert_eq!(registry.requests().len(), requests);
+        assert_eq!(fs::read(output_path).unwrap(), b"\0asm cached");
+
+        let provenance = AttestationPolicy {
+            provenance: Some(true),
+            ..Default::default()
+        };
+        let error = pull(provenance).await.unwrap_err();
+        assert!(error.contains("Attestation verification failed"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn test_cached_plugin_rechecked_against_signers() {
+        use sigstore::crypto::SigningScheme;
+
+        let registry = TestRegistry::start(None).await;
+        let image_digest = push_plugin_image(
+            &registry,
+            "org/plugin",
+            manifest::WASM_LAYER_MEDIA_TYPE,
+            b"\0asm plugin",
+        );
+        let signer = SigningScheme::ECDSA_P256_SHA256_ASN1
+            .create_signer()
+            .unwrap();
+        sign_test_image(&registry, "org/plugin", &image_digest, &signer);
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let signing_key = write_public_key(&temp_dir.path().join("cosign.pub"), &signer);
+        let other_key = write_public_key(
+            &temp_dir.path().join("other.pub"),
+            &SigningScheme::ECDSA_P256_SHA256_ASN1
+                .create_signer()
+                .unwrap(),
+        );
+        let output_path = temp_dir.path().join("plugin.wasm");
+        let output_path = output_path.to_str().unwrap();
+
+        let client = RegistryClient::new(http_config()).unwrap();
+        let pull = |verification: VerificationConfig| {
+            let client = &client;
+            let image_reference = format!("{}/org/plugin:latest", registry.address);
+            async move {
+                pull_and_extract_oci_image(
+                    &offline_cli(),
+                    client,
+                    &TrustRootCell::new(),
+                    &None,
+                    Some(&verification),
+                    &image_reference,
+                    output_path,
+                )
+                .await
+                .map_err(|e| e.to_string())
+            }
+        };
+        let skip = VerificationConfig {
+            skip: Some(true),
+            ..Default::default()
+        };
+        let signed_by = |public_key: &PathBuf| VerificationConfig {
+            public_keys: Some(vec![public_key.clone()]),
+            ..Default::default()
+        };
+
+        // Cached while verification was skipped.
+        fs::write(output_path, b"\0asm cached").unwrap();
+        fs::write(
+            verified_settings_path(output_path),
+            serde_json::to_vec(&VerifiedSettings::new(&offline_cli(), Some(&skip))).unwrap(),
+        )
+        .unwrap();
+        let requests = registry.requests().len();
+        pull(skip).await.unwrap();
+        assert_eq!(registry.requests().len(), requests);
+        assert_eq!(fs::read(output_path).unwrap(), b"\0asm cached");
+
+        // Once verification is required, the plugin is pulled and verified.
+        pull(signed_by(&signing_key)).await.unwrap();
+        assert_eq!(fs::read(output_path).unwrap(), b"\0asm plugin");
+
+        // Verified by the same key, the cache is used as is.
+        let requests = registry.requests().len();
+        pull(signed_by(&signing_key)).await.unwrap();
+        assert_eq!(registry.requests().len(), requests);
+
+        // A different signer checks the image again.
+        let error = pull(signed_by(&other_key)).await.unwrap_err();
+        assert!(error.contains("No valid signatures found"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn test_registry_client_config() {
+        let registry = TestRegistry::start(None).await;
+        push_plugin_image(
+            &registry,
+            "org/time",
+            manifest::WASM_LAYER_MEDIA_TYPE,
+            b"\0asm plugin",
+        );
+
+        let config = registry_client_config(None).unwrap();
+        assert!(matches!(config.protocol, ClientProtocol::Https));
+        assert!(config.extra_root_certificates.is_empty());
+
+        // A chain of CA certificates is split into its certificates.
+        let registries = RegistriesConfig {
+            insecure: Some(vec![registry.address.clone()]),
+            ca_certs: Some(vec![PathBuf::from("tests/fixtures/registry_ca.pem")]),
+            ..Default::default()
+        };
+        let config = registry_client_config(Some(&registries)).unwrap();
+        assert_eq!(config.extra_root_certificates.len(), 2);
+        let client = RegistryClient::new(config).unwrap();
+        assert_eq!(
+            client.url(&registry.address),
+            format!("http://{}", registry.address)
+        );
+        assert_eq!(client.url("ghcr.io"), "https://ghcr.io");
+
+        // Only the insecure registry is spoken to over plain HTTP.
+        let mut cli = offline_cli();
+        cli.insecure_skip_signature = true;
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let output_path = temp_dir.path().join("time.wasm");
+        pull_and_extract_oci_image(
+            &cli,
+            &client,
+            &TrustRootCell::new(),
+            &None,
+            None,
+            &format!("{}/org/time:latest", registry.address),
+            output_path.to_str().unwrap(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(fs::read(&output_path).unwrap(), b"\0asm plugin");
+
+        let client = RegistryClient::new(registry_client_config(None).unwrap()).unwrap();
+        fs::remove_file(&output_path).unwrap();
+        assert!(
+            pull_and_extract_oci_image(
+                &cli,
+                &client,
+                &TrustRootCell::new(),
+                &None,
+                None,
+                &format!("{}/org/time:latest", registry.address),
+                output_path.to_str().unwrap(),
+            )
+            .await
+            .is_err()
+        );
+
+        let registries = RegistriesConfig {
+            ca_certs: Some(vec![PathBuf::from("Cargo.toml")]),
+            ..Default::default()
+        };
+        let error = registry_client_config(Some(&registries)).unwrap_err();
+        assert!(
+            format!("{error:#}").contains("Invalid CA certificate Cargo.toml"),
+            "{error:#}"
+        );
+    }
 }
@@ -0,0 +1,435 @@
+//! What a plugin declares it needs, and how that compares to what its
+//! `runtime_config` grants.
+
+use crate::config::{AllowedPath, PluginName, RuntimeConfig};
+use crate::glob;
+use crate::wasm::custom_section;
+use anyhow::{Context, Result, anyhow};
+use bytesize::ByteSize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+
+/// The custom section a plugin can declare its permissions in, as JSON.
+pub const PERMISSIONS_SECTION: &str = "hyper-mcp.permissions";
+
+// The annotations of a plugin image declaring its permissions. Lists are JSON
+// arrays.
+pub const ALLOWED_HOSTS_ANNOTATION: &str = "io.hyper-mcp.plugin.allowed-hosts";
+pub const ALLOWED_PATHS_ANNOTATION: &str = "io.hyper-mcp.plugin.allowed-paths";
+pub const CONFIG_KEYS_ANNOTATION: &str = "io.hyper-mcp.plugin.config-keys";
+pub const MEMORY_LIMIT_ANNOTATION: &str = "io.hyper-mcp.plugin.memory-limit";
+
+/// The hosts, paths, config keys and memory a plugin needs.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PluginPermissions {
+    /// Hosts the plugin makes HTTP requests to, `*` matches any characters.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_hosts: Vec<String>,
+    /// Paths the plugin accesses, as the plugin sees them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_paths: Vec<String>,
+    /// Config values the plugin can't work without.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub config_keys: Vec<String>,
+    /// Memory the plugin needs at most, e.g. `64Mi`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_limit: Option<String>,
+}
+
+impl PluginPermissions {
+    /// The permissions declared in the plugin's custom section, or else in the
+    /// annotations of its image. `None` if it declares none.
+    pub fn declared(wasm: &[u8], annotations: &BTreeMap<String, String>) -> Result<Option<Self>> {
+        if let Some(section) = custom_section(wasm, PERMISSIONS_SECTION)? {
+            return serde_json::from_slice(section)
+                .with_context(|| format!("Invalid {PERMISSIONS_SECTION} section"))
+                .map(Some);
+        }
+        Self::from_annotations(annotations)
+    }
+
+    fn from_annotations(annotations: &BTreeMap<String, String>) -> Result<Option<Self>> {
+        let list = |annotation: &str| -> Result<Vec<String>> {
+            annotations
+                .get(annotation)
+                .map(|value| {
+                    serde_json::from_str(value)
+                        .with_context(|| format!("Invalid {annotation} annotation"))
+                })
+                .transpose()
+                .map(Option::unwrap_or_default)
+        };
+        let permissions = Self {
+            allowed_hosts: list(ALLOWED_HOSTS_ANNOTATION)?,
+            allowed_paths: list(ALLOWED_PATHS_ANNOTATION)?,
+            config_keys: list(CONFIG_KEYS_ANNOTATION)?,
+            memory_limit: annotations.get(MEMORY_LIMIT_ANNOTATION).cloned(),
+        };
+        Ok((permissions != Self::default()).then_some(permissions))
+    }
+
+    pub fn to_annotations(&self) -> Result<BTreeMap<String, String>> {
+        let mut annotations = BTreeMap::new();
+        for (annotation, values) in [
+            (ALLOWED_HOSTS_ANNOTATION, &self.allowed_hosts),
+            (ALLOWED_PATHS_ANNOTATION, &self.allowed_paths),
+            (CONFIG_KEYS_ANNOTATION, &self.config_keys),
+        ] {
+            if !values.is_empty() {
+                annotations.insert(annotation.to_string(), serde_json::to_string(values)?);
+            }
+        }
+        if let Some(memory_limit) = &self.memory_limit {
+            annotations.insert(MEMORY_LIMIT_ANNOTATION.to_string(), memory_limit.clone());
+        }
+        Ok(annotations)
+    }
+}
+
+/// How the grants of a plugin differ from what it declares.
+#[derive(Debug, Default, PartialEq)]
+pub struct PermissionReport {
+    /// Grants broader than declared.
+    pub broader: Vec<String>,
+    /// Declared config keys no config value is given for.
+    pub missing_config_keys: Vec<String>,
+}
+
+/// The granted paths whose `guest` side is outside every declared path,
+/// described as granted by `granted_by`.
+fn broader_paths(
+    declared: &PluginPermissions,
+    granted_by: &str,
+    paths: &[AllowedPath],
+) -> Vec<String> {
+    paths
+        .iter()
+        .filter(|path| {
+            !declared
+                .allowed_paths
+                .iter()
+                .any(|declared| Path::new(path.guest()).starts_with(declared))
+        })
+        .map(|path| {
+            format!(
+                "{granted_by} grants {}, the plugin declares {:?}",
+                path.guest(),
+                declared.allowed_paths
+            )
+        })
+        .collect()
+}
+
+/// Compares the grants of `runtime_cfg` and the config keys the plugin gets
+/// with what it declares.
+pub fn compare(
+    declared: &PluginPermissions,
+    runtime_cfg: Option<&RuntimeConfig>,
+    config_keys: &[&str],
+) -> PermissionReport {
+    let mut report = PermissionReport::default();
+
+    let granted_hosts = runtime_cfg.and_then(|rc| rc.allowed_hosts.as_ref());
+    for host in granted_hosts.into_iter().flatten() {
+        if !declared
+            .allowed_hosts
+            .iter()
+            .any(|pattern| glob::matches(pattern, host))
+        {
+            report.broader.push(format!(
+                "allowed_hosts grants {host}, the plugin declares {:?}",
+                declared.allowed_hosts
+            ));
+        }
+    }
+
+    // With client roots the paths are only a ceiling, the roots mounted
+    // under it are checked by `check_client_root_mounts`.
+    let granted_paths = runtime_cfg
+        .filter(|rc| !rc.use_client_roots.unwrap_or(false))
+        .and_then(|rc| rc.allowed_paths.as_deref());
+    report.broader.extend(broader_paths(
+        declared,
+        "allowed_paths",
+        granted_paths.unwrap_or_default(),
+    ));
+
+    let granted_memory = runtime_cfg.and_then(|rc| rc.memory_limit.as_deref());
+    if let (Some(granted), Some(needed)) = (granted_memory, &declared.memory_limit) {
+        if let (Ok(granted_bytes), Ok(needed_bytes)) =
+            (ByteSize::from_str(granted), ByteSize::from_str(needed))
+        {
+            if granted_bytes > needed_bytes {
+                report.broader.push(format!(
+                    "memory_limit grants {granted}, the plugin declares {needed}"
+                ));
+            }
+        }
+    }
+
+    report.missing_config_keys = declared
+        .config_keys
+        .iter()
+        .filter(|key| !config_keys.contains(&key.as_str()))
+        .cloned()
+        .collect();
+    report
+}
+
+/// Logs what the plugin declares and how its grants differ. Missing config
+/// keys and grants broader than declared are refused with `enforce`, and
+/// only warned about otherwise.
+pub fn check_permissions(
+    plugin_name: &PluginName,
+    declared: Option<&PluginPermissions>,
+    runtime_cfg: Option<&RuntimeConfig>,
+    config_keys: &[&str],
+    enforce: bool,
+) -> Result<()> {
+    let Some(declared) = declared else {
+        log::warn!(
+            "Plugin {plugin_name} declares no permissions, so its grants can't be checked. Plugins cached by an earlier hyper-mcp lack the annotations of their image until they are pulled again"
+        );
+        return Ok(());
+    };
+    log::info!("Plugin {plugin_name} declares permissions: {declared:?}");
+
+    let report = compare(declared, runtime_cfg, config_keys);
+    let mut problems = Vec::new();
+    if !report.missing_config_keys.is_empty() {
+        problems.push(format!(
+            "missing required config keys {:?}, set them in env_vars or env_passthrough",
+            report.missing_config_keys
+        ));
+    }
+    problems.extend(report.broader);
+    if problems.is_empty() {
+        return Ok(());
+    }
+    if enforce {
+        return Err(anyhow!(
+            "Plugin {plugin_name} is granted more than it declares or lacks config:\n  {}",
+            problems.join("\n  ")
+        ));
+    }
+    for problem in &problems {
+        log::warn!("Plugin {plugin_name}: {problem}");
+    }
+    Ok(())
+}
+
+/// Checks the client roots mounted for a session against the paths the
+/// plugin declares. Mounts outside them are refused with `enforce`, and only
+/// warned about otherwise.
+pub fn check_client_root_mounts(
+    plugin_name: &PluginName,
+    declared: Option<&PluginPermissions>,
+    mounts: &[AllowedPath],
+    enforce: bool,
+) -> Result<()> {
+    let Some(declared) = declared else {
+        return Ok(());
+    };
+    let broader = broader_paths(declared, "client roots", mounts);
+    if broader.is_empty() {
+        return Ok(());
+    }
+    if enforce {
+        return Err(anyhow!(
+            "The client roots mounted for plugin {plugin_name} are more than it declares:\n  {}",
+            broader.join("\n  ")
+        ));
+    }
+    for problem in &broader {
+        log::warn!("Plugin {plugin_name}: {problem}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wasm::tests::module_with_sections;
+
+    #[test]
+    fn test_declared_permissions() {
+        let annotations = BTreeMap::from([
+            (
+                ALLOWED_HOSTS_ANNOTATION.to_string(),
+                r#"["api.example.com"]"#.to_string(),
+            ),
+            (MEMORY_LIMIT_ANNOTATION.to_string(), "64Mi".to_string()),
+            (
+                "org.opencontainers.image.description".to_string(),
+                "A plugin".to_string(),
+            ),
+        ]);
+        let from_annotations = PluginPermissions {
+            allowed_hosts: vec!["api.example.com".to_string()],
+            memory_limit: Some("64Mi".to_string()),
+            ..Default::default()
+        };
+        let plain = module_with_sections(&[]);
+        assert_eq!(
+            PluginPermissions::declared(&plain, &annotations).unwrap(),
+            Some(from_annotations.clone())
+        );
+        assert_eq!(
+            from_annotations.to_annotations().unwrap(),
+            BTreeMap::from_iter(annotations.clone().into_iter().take(2))
+        );
+        assert_eq!(
+            PluginPermissions::declared(&plain, &BTreeMap::new()).unwrap(),
+            None
+        );
+
+        // The section in the wasm itself takes precedence.
+        let with_section = module_with_sections(&[(
+            PERMISSIONS_SECTION,
+            br#"{"config_keys": ["API_KEY"], "allowed_paths": ["/data"]}"#,
+        )]);
+        assert_eq!(
+            PluginPermissions::declared(&with_section, &annotations).unwrap(),
+            Some(PluginPermissions {
+                allowed_paths: vec!["/data".to_string()],
+                config_keys: vec!["API_KEY".to_string()],
+                ..Default::default()
+            })
+        );
+
+        let invalid = BTreeMap::from([(
+            ALLOWED_HOSTS_ANNOTATION.to_string(),
+            "api.example.com".to_string(),
+        )]);
+        assert!(PluginPermissions::declared(&plain, &invalid).is_err());
+        let invalid = module_with_sections(&[(PERMISSIONS_SECTION, br#"{"hosts": []}"#)]);
+        assert!(PluginPermissions::declared(&invalid, &BTreeMap::new()).is_err());
+    }
+
+    #[test]
+    fn test_compare() {
+        let declared = PluginPermissions {
+            allowed_hosts: vec!["*.example.com".to_string(), "1.1.1.1".to_string()],
+            allowed_paths: vec!["/data".to_string()],
+            config_keys: vec!["API_KEY".to_string(), "REGION".to_string()],
+            memory_limit: Some("64Mi".to_string()),
+        };
+        let runtime_cfg = |hosts: &[&str], paths: &[&str], memory_limit: &str| RuntimeConfig {
+            allowed_hosts: Some(hosts.iter().map(|host| host.to_string()).collect()),
+            allowed_paths: Some(
+                paths
+                    .iter()
+                    .map(|path| AllowedPath::from_str(path).unwrap())
+                    .collect(),
+            ),
+            memory_limit: Some(memory_limit.to_string()),
+            ..Default::default()
+        };
+
+        let narrower = runtime_cfg(
+            &["api.example.com", "1.1.1.1"],
+            &["/home/me/project:/data/project"],
+            "32Mi",
+        );
+        assert_eq!(
+            compare(&declared, Some(&narrower), &["API_KEY", "REGION"]),
+            PermissionReport::default()
+        );
+        assert_eq!(
+            compare(&declared, None, &["API_KEY"]).missing_config_keys,
+            ["REGION"]
+        );
+
+        let broader = runtime_cfg(
+            &["*", "api.example.com"],
+            &["/home/me", "/srv:/data"],
+            "1Gi",
+        );
+        let report = compare(&declared, Some(&broader), &["API_KEY", "REGION", "OTHER"]);
+        assert_eq!(report.broader.len(), 3, "{report:?}");
+        assert!(report.broader[0].starts_with("allowed_hosts grants *"));
+        let client_roots = RuntimeConfig {
+            use_client_roots: Some(true),
+            ..runtime_cfg(&[], &["/home/me"], "32Mi")
+        };
+        assert!(
+            compare(&declared, Some(&client_roots), &["API_KEY", "REGION"])
+                .broader
+                .is_empty()
+        );
+        assert!(report.broader[1].starts_with("allowed_paths grants /home/me"));
+        assert_eq!(
+            report.broader[2],
+            "memory_limit grants 1Gi, the plugin declares 64Mi"
+        );
+        assert!(report.missing_config_keys.is_empty());
+    }
+
+    #[test]
+    fn test_check_permissions() {
+        let plugin_name = PluginName::try_from("plugin").unwrap();
+        let declared = PluginPermissions {
+            allowed_hosts: vec!["api.example.com".to_string()],
+            config_keys: vec!["API_KEY".to_string()],
+            ..Default::default()
+        };
+        let runtime_cfg = RuntimeConfig {
+            allowed_hosts: Some(vec!["*".to_string()]),
+            ..Default::default()
+        };
+
+        // Only warned about unless enforced.
+        check_permissions(
+            &plugin_name,
+            Some(&declared),
+            Some(&runtime_cfg),
+            &[],
+            false,
+        )
+        .unwrap();
+        let error = check_permissions(&plugin_name, Some(&declared), Some(&runtime_cfg), &[], true)
+            .unwrap_err()
+            .to_string();
+        let lines: Vec<&str> = error.lines().collect();
+        assert_eq!(lines.len(), 3, "{error}");
+        assert!(lines[1].contains(r#"missing required config keys ["API_KEY"]"#));
+        assert!(lines[2].contains("allowed_hosts grants *"));
+
+        check_permissions(&plugin_name, Some(&declared), None, &["API_KEY"], true).unwrap();
+        // Plugins that declare nothing load as before.
+        check_permissions(&plugin_name, None, Some(&runtime_cfg), &[], true).unwrap();
+    }
+
+    #[test]
+    fn test_check_client_root_mounts() {
+        let plugin_name = PluginName::try_from("plugin").unwrap();
+        let declared = PluginPermissions {
+            allowed_paths: vec!["/home/me/project".to_string()],
+            ..Default::default()
+        };
+        let mounts = |paths: &[&str]| -> Vec<AllowedPath> {
+            paths
+                .iter()
+                .map(|path| AllowedPath::from_str(path).unwrap())
+                .collect()
+        };
+
+        let inside = mounts(&["/home/me/project/src"]);
+        check_client_root_mounts(&plugin_name, Some(&declared), &inside, true).unwrap();
+
+        let outside = mounts(&["/home/me/project", "/home/me/.ssh"]);
+        check_client_root_mounts(&plugin_name, Some(&declared), &outside, false).unwrap();
+        let error = check_client_root_mounts(&plugin_name, Some(&declared), &outside, true)
+            .unwrap_err()
+            .to_string();
+        let lines: Vec<&str> = error.lines().collect();
+        assert_eq!(lines.len(), 2, "{error}");
+        assert!(lines[1].contains("client roots grants /home/me/.ssh"));
+
+        // Plugins that declare nothing mount every root, as before.
+        check_client_root_mounts(&plugin_name, None, &outside, true).unwrap();
+    }
+}
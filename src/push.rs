@@ -0,0 +1,410 @@
+use crate::Cli;
+use crate::config::{Config, PluginConfig, PluginName, default_config_path};
+use crate::oci::{RegistryClient, build_auth, registry_client_config};
+use crate::permissions::PluginPermissions;
+use crate::plugins::{PluginService, load_cli_config};
+use anyhow::{Context, Result, anyhow};
+use clap::Args;
+use flate2::{Compression, write::GzEncoder};
+use oci_client::Reference;
+use oci_client::client::{Config as OciConfig, ImageLayer};
+use oci_client::manifest::{self, OciImageManifest, OciManifest};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+pub const DESCRIPTION_ANNOTATION: &str = "org.opencontainers.image.description";
+pub const SOURCE_ANNOTATION: &str = "org.opencontainers.image.source";
+const TITLE_ANNOTATION: &str = "org.opencontainers.image.title";
+// A JSON array of the tools a plugin provides.
+pub const TOOLS_ANNOTATION: &str = "io.hyper-mcp.plugin.tools";
+
+#[derive(Args, Clone, Debug)]
+pub struct PushArgs {
+    /// The plugin's .wasm file
+    pub wasm: PathBuf,
+
+    /// Image reference to push to, e.g. ghcr.io/org/plugin:1.0
+    pub reference: String,
+
+    #[arg(long, help = "Description of the plugin")]
+    pub description: Option<String>,
+
+    #[arg(long, help = "URL of the plugin's source repository")]
+    pub source: Option<String>,
+
+    #[arg(
+        long = "allowed-host",
+        value_name = "HOST",
+        help = "A host the plugin needs to reach, may be repeated"
+    )]
+    pub allowed_hosts: Vec<String>,
+
+    #[arg(
+        long = "allowed-path",
+        value_name = "PATH",
+        help = "A path the plugin needs access to, may be repeated"
+    )]
+    pub allowed_paths: Vec<String>,
+
+    #[arg(
+        long = "config-key",
+        value_name = "KEY",
+        help = "A config key the plugin requires, may be repeated"
+    )]
+    pub config_keys: Vec<String>,
+
+    #[arg(long, help = "The most memory the plugin needs, e.g. 64Mi")]
+    pub memory_limit: Option<String>,
+
+    #[arg(
+        long,
+        help = "Push plugin.wasm in a tar+gzip layer, which older hyper-mcp versions can load"
+    )]
+    pub tar: bool,
+
+    #[arg(long, help = "Push over plain HTTP, e.g. to a local registry")]
+    pub insecure: bool,
+}
+
+/// The config, if there is one, for its `auths` and `registries`.
+async fn optional_config(cli: &Cli) -> Result<Config> {
+    if cli.config_file.is_none() && cli.config_dir.is_none() && !default_config_path().exists() {
+        return Ok(Config::default());
+    }
+    load_cli_config(cli).await
+}
+
+/// The names of the tools the plugin's `describe` returns.
+async fn describe(cli: &Cli, wasm_path: &Path) -> Result<Vec<String>> {
+    let wasm_path = std::path::absolute(wasm_path)?;
+    let plugin_name = PluginName::try_from("plugin")?;
+    let config = Config {
+        plugins: HashMap::from([(
+            plugin_name.clone(),
+            PluginConfig {
+                url: Url::from_file_path(&wasm_path)
+                    .map_err(|_| anyhow!("Can't load {}", wasm_path.display()))?,
+                runtime_config: None,
+                verification: None,
+            },
+        )]),
+        ..Default::default()
+    };
+    // Only the tool names are read, the plugin isn't granted anything, so
+    // there is no consent to enforce.
+    let cli = Cli {
+        enforce_permissions: false,
+        ..cli.clone()
+    };
+    PluginService::with_config(config, &cli)
+        .await?
+        .describe_tool_names(&plugin_name)
+        .await
+}
+
+/// The annotations of the pushed manifest.
+fn annotations(args: &PushArgs, tools: &[String]) -> Result<BTreeMap<String, String>> {
+    let mut annotations = BTreeMap::new();
+    if let Some(description) = &args.description {
+        annotations.insert(DESCRIPTION_ANNOTATION.to_string(), description.clone());
+    }
+    if let Some(source) = &args.source {
+        annotations.insert(SOURCE_ANNOTATION.to_string(), source.clone());
+    }
+    let permissions = PluginPermissions {
+        allowed_hosts: args.allowed_hosts.clone(),
+        allowed_paths: args.allowed_paths.clone(),
+        config_keys: args.config_keys.clone(),
+        memory_limit: args.memory_limit.clone(),
+    };
+    annotations.extend(permissions.to_annotations()?);
+    annotations.insert(TOOLS_ANNOTATION.to_string(), serde_json::to_string(tools)?);
+    Ok(annotations)
+}
+
+/// The plugin as a layer, and the config blob that goes with it: a wasm
+/// artifact, or with `tar` a single layer image holding `plugin.wasm`.
+fn layer_and_config(wasm: Vec<u8>, tar: bool) -> Result<(ImageLayer, OciConfig)> {
+    if !tar {
+        let annotations =
+            BTreeMap::from([(TITLE_ANNOTATION.to_string(), "plugin.wasm".to_string())]);
+        return Ok((
+            ImageLayer::new(
+                wasm,
+                manifest::WASM_LAYER_MEDIA_TYPE.to_string(),
+                Some(annotations),
+            ),
+            OciConfig::new(
+                b"{}".to_vec(),
+                manifest::WASM_CONFIG_MEDIA_TYPE.to_string(),
+                None,
+            ),
+        ));
+    }
+
+    let mut header = tar::Header::new_gnu();
+    header.set_size(wasm.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    let mut archive = tar::Builder::new(Vec::new());
+    archive.append_data(&mut header, "plugin.wasm", wasm.as_slice())?;
+    let archive = archive.into_inner()?;
+    let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+    gzip.write_all(&archive)?;
+
+    let config = serde_json::json!({
+        "architecture": "wasm",
+        "os": "wasip1",
+        "rootfs": {
+            "type": "layers",
+            "diff_ids": [format!("sha256:{}", hex::encode(Sha256::digest(&archive)))]
+        }
+    });
+    Ok((
+        ImageLayer::new(
+            gzip.finish()?,
+            manifest::IMAGE_LAYER_GZIP_MEDIA_TYPE.to_string(),
+            None,
+        ),
+        OciConfig::new(
+            serde_json::to_vec(&config)?,
+            manifest::IMAGE_CONFIG_MEDIA_TYPE.to_string(),
+            None,
+        ),
+    ))
+}
+
+/// Pushes the plugin and returns the reference to its manifest by digest.
+pub async fn push(cli: &Cli, config: &Config, args: &PushArgs) -> Result<Reference> {
+    let wasm = std::fs::read(&args.wasm)
+        .with_context(|| format!("Failed to read {}", args.wasm.display()))?;
+    if !wasm.starts_with(b"\0asm") {
+        return Err(anyhow!("{} is not a wasm module", args.wasm.display()));
+    }
+    let tools = describe(cli, &args.wasm)
+        .await
+        .with_context(|| format!("Failed to describe {}", args.wasm.display()))?;
+
+    let reference: Reference = args
+        .reference
+        .trim_start_matches("oci://")
+        .parse()
+        .with_context(|| format!("Invalid image reference {}", args.reference))?;
+    let mut client_config = registry_client_config(config.registries.as_ref())?;
+    if args.insecure {
+        client_config.protocol = sigstore::registry::ClientProtocol::Http;
+    }
+    let registry = RegistryClient::new(client_config)?;
+    let auth = build_auth(&registry, &reference, &config.auths, "pull,push").await;
+
+    let (layer, oci_config) = layer_and_config(wasm, args.tar)?;
+    let layers = [layer];
+    let manifest = OciImageManifest::build(&layers, &oci_config, Some(annotations(args, &tools)?));
+
+    // The blobs and manifest are pushed one by one, so the digest is the one
+    // of the exact manifest bytes sent rather than a second lookup of the tag.
+    let client = &registry.client;
+    client
+        .store_auth_if_needed(reference.resolve_registry(), &auth)
+        .await;
+    let digest = async {
+        for layer in &layers {
+            client
+                .push_blob(&reference, &layer.data, &layer.sha256_digest())
+                .await?;
+        }
+        client
+            .push_blob(&reference, &oci_config.data, &manifest.config.digest)
+            .await?;
+        let manifest = OciManifest::Image(manifest);
+        let body = serde_json::to_vec(&manifest)?;
+        let digest = format!("sha256:{}", hex::encode(Sha256::digest(&body)));
+        client
+            .push_manifest_raw(&reference, body, manifest.content_type().parse()?)
+            .await?;
+        anyhow::Ok(digest)
+    }
+    .await
+    .with_context(|| format!("Failed to push {reference}"))?;
+    Ok(Reference::with_digest(
+        reference.registry().to_string(),
+        reference.repository().to_string(),
+        digest,
+    ))
+}
+
+/// `hyper-mcp plugin push`
+pub async fn run(cli: &Cli, args: PushArgs) -> Result<()> {
+    let config = optional_config(cli).await?;
+    let pushed = push(cli, &config, &args).await?;
+    eprintln!("Pushed {}", args.reference);
+    println!("{pushed}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AuthConfig;
+    use crate::oci::{TrustRootCell, cached_annotations, pull_and_extract_oci_image};
+    use crate::permissions::{
+        ALLOWED_HOSTS_ANNOTATION, ALLOWED_PATHS_ANNOTATION, CONFIG_KEYS_ANNOTATION,
+    };
+    use crate::test_registry::TestRegistry;
+    use clap::Parser;
+
+    fn time_wasm() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("examples/plugins/time/time.wasm")
+    }
+
+    #[tokio::test]
+    async fn test_push() {
+        let registry = TestRegistry::start(Some(("admin", "secret"))).await;
+        let cli = Cli::parse_from(["hyper-mcp", "--insecure-skip-signature"]);
+        let config = Config {
+            auths: Some(HashMap::from([(
+                Url::parse(&format!("https://{}", registry.address)).unwrap(),
+                AuthConfig::Basic {
+                    username: "admin".to_string(),
+                    password: "secret".to_string(),
+                },
+            )])),
+            ..Default::default()
+        };
+        let push_args = |repository: &str, tar: bool| PushArgs {
+            wasm: time_wasm(),
+            reference: format!("oci://{}/{repository}:1.0", registry.address),
+            description: Some("What time is it".to_string()),
+            source: Some("https://github.com/tuananh/hyper-mcp".to_string()),
+            allowed_hosts: vec!["worldtimeapi.org".to_string()],
+            allowed_paths: Vec::new(),
+            config_keys: vec!["TZ".to_string()],
+            memory_limit: None,
+            tar,
+            insecure: true,
+        };
+
+        let pushed = push(&cli, &config, &push_args("org/time", false))
+            .await
+            .unwrap();
+        assert_eq!(pushed.repository(), "org/time");
+        // The digest comes from the push itself, not a lookup of the tag.
+        assert!(
+            !registry
+                .requests()
+                .iter()
+                .any(|request| request.ends_with("/manifests/1.0") && !request.starts_with("PUT")),
+            "{:?}",
+            registry.requests()
+        );
+
+        let client = RegistryClient::new(sigstore::registry::ClientConfig {
+            protocol: sigstore::registry::ClientProtocol::Http,
+            ..Default::default()
+        })
+        .unwrap();
+        let auth =
+            oci_client::secrets::RegistryAuth::Basic("admin".to_string(), "secret".to_string());
+        let (manifest, _) = client
+            .client
+            .pull_image_manifest(&pushed, &auth)
+            .await
+            .unwrap();
+        assert_eq!(manifest.layers.len(), 1);
+        assert_eq!(
+            manifest.layers[0].media_type,
+            manifest::WASM_LAYER_MEDIA_TYPE
+        );
+        let annotations = manifest.annotations.unwrap();
+        assert_eq!(annotations[DESCRIPTION_ANNOTATION], "What time is it");
+        assert_eq!(
+            annotations[SOURCE_ANNOTATION],
+            "https://github.com/tuananh/hyper-mcp"
+        );
+        assert_eq!(
+            annotations[ALLOWED_HOSTS_ANNOTATION],
+            r#"["worldtimeapi.org"]"#
+        );
+        assert!(!annotations.contains_key(ALLOWED_PATHS_ANNOTATION));
+        assert_eq!(annotations[CONFIG_KEYS_ANNOTATION], r#"["TZ"]"#);
+        let tools: Vec<String> = serde_json::from_str(&annotations[TOOLS_ANNOTATION]).unwrap();
+        assert_eq!(tools, ["time"]);
+
+        // Both layouts load like any other plugin.
+        push(&cli, &config, &push_args("org/time-tar", true))
+            .await
+            .unwrap();
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        for repository in ["org/time", "org/time-tar"] {
+            let output_path = temp_dir
+                .path()
+                .join(format!("{}.wasm", repository.replace('/', "-")));
+            pull_and_extract_oci_image(
+                &cli,
+                &client,
+                &TrustRootCell::new(),
+                &config.auths,
+                None,
+                &format!("{}/{repository}:1.0", registry.address),
+                output_path.to_str().unwrap(),
+            )
+            .await
+            .unwrap();
+            assert_eq!(
+                std::fs::read(&output_path).unwrap(),
+                std::fs::read(time_wasm()).unwrap()
+            );
+            // The annotations stay with the cached plugin, for its declared
+            // permissions.
+            let cached = cached_annotations(output_path.to_str().unwrap()).unwrap();
+            assert_eq!(
+                PluginPermissions::declared(&std::fs::read(time_wasm()).unwrap(), &cached)
+                    .unwrap()
+                    .unwrap()
+                    .allowed_hosts,
+                ["worldtimeapi.org"]
+            );
+        }
+
+        // Declared permissions aren't enforced just to read the tool names.
+        let mut wasm = std::fs::read(time_wasm()).unwrap();
+        wasm.extend_from_slice(
+            &crate::wasm::tests::module_with_sections(&[(
+                crate::permissions::PERMISSIONS_SECTION,
+                br#"{"config_keys": ["TZ"]}"#,
+            )])[8..],
+        );
+        let declaring_wasm = temp_dir.path().join("declaring.wasm");
+        std::fs::write(&declaring_wasm, wasm).unwrap();
+        let mut enforcing_cli = cli.clone();
+        enforcing_cli.enforce_permissions = true;
+        push(
+            &enforcing_cli,
+            &config,
+            &PushArgs {
+                wasm: declaring_wasm,
+                ..push_args("org/declaring", false)
+            },
+        )
+        .await
+        .unwrap();
+
+        let error = push(
+            &cli,
+            &config,
+            &PushArgs {
+                wasm: PathBuf::from("Cargo.toml"),
+                ..push_args("org/broken", false)
+            },
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            error.to_string().contains("is not a wasm module"),
+            "{error}"
+        );
+    }
+}
@@ -0,0 +1,101 @@
+//! An MCP client connected to a bare server over an in-memory pipe, for tests
+//! of what plugins ask of the client through their host functions.
+
+use rmcp::{
+    ClientHandler, ErrorData as McpError, Peer, RoleServer, ServerHandler, ServiceExt,
+    model::{
+        ClientCapabilities, ClientInfo, Content, CreateElicitationRequestParam,
+        CreateElicitationResult, CreateMessageRequestParam, CreateMessageResult,
+        ElicitationCapability, ProgressNotificationParam, Role, SamplingMessage,
+    },
+    service::{NotificationContext, RequestContext, RoleClient, RunningService},
+};
+use std::sync::{Arc, Mutex};
+
+struct TestServer;
+
+impl ServerHandler for TestServer {}
+
+/// A client that supports sampling and answers every elicitation with
+/// `elicitation`, if they are set. The progress it's notified of is kept in
+/// `progress`.
+#[derive(Clone, Default)]
+pub struct TestClient {
+    pub sampling: bool,
+    pub elicitation: Option<CreateElicitationResult>,
+    pub progress: Arc<Mutex<Vec<ProgressNotificationParam>>>,
+}
+
+impl ClientHandler for TestClient {
+    fn get_info(&self) -> ClientInfo {
+        ClientInfo {
+            capabilities: ClientCapabilities {
+                sampling: self.sampling.then(Default::default),
+                elicitation: self
+                    .elicitation
+                    .as_ref()
+                    .map(|_| ElicitationCapability::default()),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    async fn create_message(
+        &self,
+        params: CreateMessageRequestParam,
+        _context: RequestContext<RoleClient>,
+    ) -> Result<CreateMessageResult, McpError> {
+        Ok(CreateMessageResult {
+            model: "test-model".to_string(),
+            stop_reason: Some(CreateMessageResult::STOP_REASON_END_TURN.to_string()),
+            message: SamplingMessage {
+                role: Role::Assistant,
+                content: Content::text(format!("{} messages", params.messages.len())),
+            },
+        })
+    }
+
+    async fn create_elicitation(
+        &self,
+        _request: CreateElicitationRequestParam,
+        _context: RequestContext<RoleClient>,
+    ) -> Result<CreateElicitationResult, McpError> {
+        self.elicitation
+            .clone()
+            .ok_or_else(|| McpError::internal_error("Elicitation is not supported", None))
+    }
+
+    async fn on_progress(
+        &self,
+        params: ProgressNotificationParam,
+        _context: NotificationContext<RoleClient>,
+    ) {
+        self.progress.lock().unwrap().push(params);
+    }
+}
+
+/// The server side peer of `client`, once it's initialized. The client keeps
+/// running as long as the returned service is kept.
+pub async fn connect(
+    client: TestClient,
+) -> (Peer<RoleServer>, RunningService<RoleClient, TestClient>) {
+    connect_to(TestServer, client).await
+}
+
+/// Like `connect`, with `server` answering the client's requests.
+pub async fn connect_to(
+    server: impl ServerHandler,
+    client: TestClient,
+) -> (Peer<RoleServer>, RunningService<RoleClient, TestClient>) {
+    let (server_transport, client_transport) = tokio::io::duplex(64 * 1024);
+    let (server, client) = tokio::join!(
+        server.serve(server_transport),
+        client.serve(client_transport)
+    );
+    let server = server.expect("Test server failed to initialize");
+    let peer = server.peer().clone();
+    // The server loop runs until the client goes away.
+    tokio::spawn(server.waiting());
+    (peer, client.expect("Test client failed to initialize"))
+}
@@ -0,0 +1,246 @@
+//! An in-memory OCI distribution registry for tests, serving plain HTTP on a
+//! local port. It implements just enough of the API for oci-client and the
+//! sigstore client: manifests by tag or digest, blobs, and blob uploads.
+
+use axum::{
+    Router,
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, Method, StatusCode, Uri, header},
+    response::{IntoResponse, Response},
+    routing::any,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+#[derive(Default)]
+struct Registry {
+    // Basic auth every request must carry, as the `Authorization` header value.
+    authorization: Option<String>,
+    // Keyed by (repository, tag or digest).
+    manifests: HashMap<(String, String), (String, Vec<u8>)>,
+    blobs: HashMap<String, Vec<u8>>,
+    uploads: HashMap<String, Vec<u8>>,
+    next_upload: usize,
+    // `METHOD /path` of every authorized request.
+    requests: Vec<String>,
+}
+
+type SharedRegistry = Arc<Mutex<Registry>>;
+
+pub struct TestRegistry {
+    /// `host:port` the registry listens on.
+    pub address: String,
+    registry: SharedRegistry,
+}
+
+pub fn sha256_digest(data: &[u8]) -> String {
+    format!("sha256:{}", hex::encode(Sha256::digest(data)))
+}
+
+impl TestRegistry {
+    /// Starts a registry, requiring basic auth when `credentials` are given.
+    pub async fn start(credentials: Option<(&str, &str)>) -> Self {
+        use base64::Engine;
+
+        let registry = SharedRegistry::default();
+        registry.lock().unwrap().authorization = credentials.map(|(username, password)| {
+            let encoded =
+                base64::engine::general_purpose::STANDARD.encode(format!("{username}:{password}"));
+            format!("Basic {encoded}")
+        });
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let app = Router::new()
+            .route("/v2/", any(handle))
+            .route("/v2/{*path}", any(handle))
+            .with_state(Arc::clone(&registry));
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        Self { address, registry }
+    }
+
+    /// Stores a blob and returns its digest.
+    pub fn push_blob(&self, data: &[u8]) -> String {
+        let digest = sha256_digest(data);
+        self.registry
+            .lock()
+            .unwrap()
+            .blobs
+            .insert(digest.clone(), data.to_vec());
+        digest
+    }
+
+    /// Stores a manifest under `tag` and its digest, and returns the digest.
+    pub fn push_manifest(
+        &self,
+        repository: &str,
+        tag: &str,
+        media_type: &str,
+        manifest: &serde_json::Value,
+    ) -> String {
+        let manifest = serde_json::to_vec(manifest).unwrap();
+        let digest = sha256_digest(&manifest);
+        let mut registry = self.registry.lock().unwrap();
+        for reference in [tag, &digest] {
+            registry.manifests.insert(
+                (repository.to_string(), reference.to_string()),
+                (media_type.to_string(), manifest.clone()),
+            );
+        }
+        digest
+    }
+
+    /// `METHOD /path` of every authorized request so far.
+    pub fn requests(&self) -> Vec<String> {
+        self.registry.lock().unwrap().requests.clone()
+    }
+}
+
+fn query_param<'a>(uri: &'a Uri, name: &str) -> Option<&'a str> {
+    uri.query()?.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        (key == name).then_some(value)
+    })
+}
+
+async fn handle(
+    State(registry): State<SharedRegistry>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let mut registry = registry.lock().unwrap();
+    if let Some(authorization) = &registry.authorization {
+        if headers
+            .get(header::AUTHORIZATION)
+            .is_none_or(|value| value != authorization.as_str())
+        {
+            return (
+                StatusCode::UNAUTHORIZED,
+                [(header::WWW_AUTHENTICATE, r#"Basic realm="registry""#)],
+            )
+                .into_response();
+        }
+    }
+    registry.requests.push(format!("{method} {}", uri.path()));
+
+    let path = uri.path().trim_start_matches("/v2/");
+    if path.is_empty() {
+        return StatusCode::OK.into_response();
+    }
+
+    if let Some((repository, reference)) = path.split_once("/manifests/") {
+        let key = (repository.to_string(), reference.to_string());
+        return match method {
+            Method::PUT => {
+                let media_type = headers
+                    .get(header::CONTENT_TYPE)
+                    .and_then(|value| value.to_str().ok())
+                    .unwrap_or_default()
+                    .to_string();
+                let digest = sha256_digest(&body);
+                for reference in [reference, &digest] {
+                    registry.manifests.insert(
+                        (repository.to_string(), reference.to_string()),
+                        (media_type.clone(), body.to_vec()),
+                    );
+                }
+                (
+                    StatusCode::CREATED,
+                    [
+                        (
+                            header::LOCATION,
+                            format!("/v2/{repository}/manifests/{digest}"),
+                        ),
+                        (
+                            header::HeaderName::from_static("docker-content-digest"),
+                            digest,
+                        ),
+                    ],
+                )
+                    .into_response()
+            }
+            _ => match registry.manifests.get(&key) {
+                Some((media_type, manifest)) => (
+                    [
+                        (header::CONTENT_TYPE, media_type.clone()),
+                        (
+                            header::HeaderName::from_static("docker-content-digest"),
+                            sha256_digest(manifest),
+                        ),
+                    ],
+                    manifest.clone(),
+                )
+                    .into_response(),
+                None => StatusCode::NOT_FOUND.into_response(),
+            },
+        };
+    }
+
+    if let Some((repository, upload)) = path.split_once("/blobs/uploads/") {
+        if method == Method::POST {
+            registry.next_upload += 1;
+            let id = registry.next_upload.to_string();
+            registry.uploads.insert(id.clone(), body.to_vec());
+            return (
+                StatusCode::ACCEPTED,
+                [
+                    (
+                        header::LOCATION,
+                        format!("/v2/{repository}/blobs/uploads/{id}"),
+                    ),
+                    (header::RANGE, "0-0".to_string()),
+                ],
+            )
+                .into_response();
+        }
+        let Some(data) = registry.uploads.get_mut(upload) else {
+            return StatusCode::NOT_FOUND.into_response();
+        };
+        data.extend_from_slice(&body);
+        let location = format!("/v2/{repository}/blobs/uploads/{upload}");
+        if method == Method::PATCH {
+            let range = format!("0-{}", data.len().saturating_sub(1));
+            return (
+                StatusCode::ACCEPTED,
+                [(header::LOCATION, location), (header::RANGE, range)],
+            )
+                .into_response();
+        }
+        let Some(digest) = query_param(&uri, "digest").map(|d| d.replace("%3A", ":")) else {
+            return StatusCode::BAD_REQUEST.into_response();
+        };
+        let data = registry.uploads.remove(upload).unwrap_or_default();
+        if sha256_digest(&data) != digest {
+            return StatusCode::BAD_REQUEST.into_response();
+        }
+        registry.blobs.insert(digest.clone(), data);
+        return (
+            StatusCode::CREATED,
+            [(header::LOCATION, format!("/v2/{repository}/blobs/{digest}"))],
+        )
+            .into_response();
+    }
+
+    if let Some((_, digest)) = path.split_once("/blobs/") {
+        return match registry.blobs.get(digest) {
+            Some(blob) => (
+                [(
+                    header::HeaderName::from_static("docker-content-digest"),
+                    digest.to_string(),
+                )],
+                blob.clone(),
+            )
+                .into_response(),
+            None => StatusCode::NOT_FOUND.into_response(),
+        };
+    }
+
+    StatusCode::NOT_FOUND.into_response()
+}
@@ -0,0 +1,976 @@
+use crate::{
+    Cli,
+    config::{
+        Config, ConfigFile, ConfigFileError, ConflictingEntryError, DuplicatePluginError,
+        InterpolationError, PluginConfig, PluginName, RegistriesConfig, VerificationConfig,
+        read_config_tree,
+    },
+    oci::{IdentityVerifier, PublicKeysVerifier, find_registry_auth, registry_client_config},
+    plugins::{PluginService, cli_config_files},
+};
+use anyhow::Result;
+use bytesize::ByteSize;
+use oci_client::Reference;
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use url::Url;
+
+#[derive(Debug, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A problem found in a config file, with the 1-based line and column it
+/// refers to when it could be found in the file.
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub file: PathBuf,
+    pub severity: Severity,
+    pub position: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", self.file.display())?;
+        if let Some((line, column)) = self.position {
+            write!(f, "{line}:{column}:")?;
+        }
+        write!(f, " {}: {}", self.severity, self.message)
+    }
+}
+
+#[derive(Default)]
+struct Validator {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Validator {
+    /// Records a problem with the value at `keys` (and, for list items, the
+    /// item `value`) in `path`.
+    fn report(
+        &mut self,
+        path: &Path,
+        content: &str,
+        severity: Severity,
+        keys: &[&str],
+        value: Option<&str>,
+        message: String,
+    ) {
+        self.diagnostics.push(Diagnostic {
+            file: path.to_path_buf(),
+            severity,
+            position: locate(content, keys, value),
+            message,
+        });
+    }
+
+    fn report_at(&mut self, path: &Path, position: Position, message: String) {
+        self.diagnostics.push(Diagnostic {
+            file: path.to_path_buf(),
+            severity: Severity::Error,
+            position,
+            message,
+        });
+    }
+
+    /// Records a config file `read_config_tree` couldn't read, at the value
+    /// the loader's error points to.
+    fn report_file_error(&mut self, e: ConfigFileError) {
+        let position = e
+            .content
+            .as_deref()
+            .and_then(|content| error_position(content, &e.error));
+        let message = if let Some(e) = e.error.downcast_ref::<InterpolationError>() {
+            format!("{}: {:#}", e.location, e.error)
+        } else if let Some(e) = e.error.downcast_ref::<toml::de::Error>() {
+            e.message().to_string()
+        } else {
+            format!("{:#}", e.error)
+        };
+        self.report_at(&e.path, position, message);
+    }
+
+    /// Merges the files with `Config::merge`, reporting each plugin that is
+    /// defined more than once and each entry set differently in another file.
+    fn merge(&mut self, files: &[ConfigFile]) -> Config {
+        let mut merged = Config::default();
+        for file in files {
+            let mut config = file.config.clone();
+            while let Err(e) = merged.merge(config.clone(), &file.path) {
+                if let Some(conflict) = e.downcast_ref::<ConflictingEntryError>() {
+                    let keys: Vec<_> = conflict.keys.iter().map(String::as_str).collect();
+                    self.report(
+                        &file.path,
+                        &file.content,
+                        Severity::Error,
+                        &keys,
+                        None,
+                        format!(
+                            "{}: already set differently in another config file",
+                            keys.join(".")
+                        ),
+                    );
+                    let removed = match keys[..] {
+                        ["verification", prefix] => config
+                            .verification
+                            .as_mut()
+                            .and_then(|verification| verification.remove(prefix))
+                            .map(drop),
+                        ["registries", "mirrors", prefix] => config
+                            .registries
+                            .as_mut()
+                            .and_then(|registries| registries.mirrors.as_mut())
+                            .and_then(|mirrors| mirrors.remove(prefix))
+                            .map(drop),
+                        _ => None,
+                    };
+                    if removed.is_none() {
+                        break;
+                    }
+                    continue;
+                }
+                let Some(duplicate) = e.downcast_ref::<DuplicatePluginError>() else {
+                    self.report_at(&file.path, None, format!("{e:#}"));
+                    break;
+                };
+                let plugin_name = &duplicate.plugin_name;
+                self.report(
+                    &file.path,
+                    &file.content,
+                    Severity::Error,
+                    &["plugins", plugin_name.as_str()],
+                    None,
+                    format!(
+                        "plugins.{plugin_name}: already defined in {}",
+                        duplicate.defined_in
+                    ),
+                );
+                config.plugins.remove(plugin_name);
+            }
+        }
+        merged
+    }
+
+    /// Identity patterns must compile and public keys must be readable PEM keys.
+    fn check_verification(
+        &mut self,
+        path: &Path,
+        content: &str,
+        keys: &[&str],
+        verification: &VerificationConfig,
+    ) {
+        let location = keys.join(".");
+        if let Some(identities) = &verification.identities {
+            if let Err(e) = IdentityVerifier::new(identities) {
+                self.report(
+                    path,
+                    content,
+                    Severity::Error,
+                    &[keys, &["identities"]].concat(),
+                    None,
+                    format!("{location}.identities: {e:#}"),
+                );
+            }
+        }
+        for public_key in verification.public_keys.iter().flatten() {
+            if let Err(e) = PublicKeysVerifier::new(std::slice::from_ref(public_key)) {
+                self.report(
+                    path,
+                    content,
+                    Severity::Error,
+                    &[keys, &["public_keys"]].concat(),
+                    None,
+                    format!("{location}.public_keys: {e:#}"),
+                );
+            }
+        }
+    }
+
+    /// Insecure registries must be bare hosts and CA certificates readable PEM
+    /// certificates.
+    fn check_registries(&mut self, path: &Path, content: &str, registries: &RegistriesConfig) {
+        for registry in registries.insecure.iter().flatten() {
+            if registry.contains('/') {
+                self.report(
+                    path,
+                    content,
+                    Severity::Error,
+                    &["registries", "insecure"],
+                    Some(registry),
+                    format!("registries.insecure: {registry} must be a host or host:port"),
+                );
+            }
+        }
+        for ca_cert in registries.ca_certs.iter().flatten() {
+            let single = RegistriesConfig {
+                ca_certs: Some(vec![ca_cert.clone()]),
+                ..Default::default()
+            };
+            if let Err(e) = registry_client_config(Some(&single)) {
+                self.report(
+                    path,
+                    content,
+                    Severity::Error,
+                    &["registries", "ca_certs"],
+                    None,
+                    format!("registries.ca_certs: {e:#}"),
+                );
+            }
+        }
+    }
+
+    fn check_semantics(&mut self, source: &ConfigFile, merged: &Config) {
+        let (path, content) = (source.path.as_path(), source.content.as_str());
+        let mut prefixes: Vec<_> = source.config.verification.iter().flatten().collect();
+        prefixes.sort_by_key(|(prefix, _)| prefix.as_str());
+        for (prefix, verification) in prefixes {
+            self.check_verification(path, content, &["verification", prefix], verification);
+        }
+        if let Some(registries) = &source.config.registries {
+            self.check_registries(path, content, registries);
+        }
+
+        for (plugin_name, plugin_cfg) in sorted_plugins(&source.config) {
+            let name = plugin_name.as_str();
+            if let Some(verification) = &plugin_cfg.verification {
+                if plugin_cfg.url.scheme() != "oci" {
+                    self.report(
+                        path,
+                        content,
+                        Severity::Warning,
+                        &["plugins", name, "verification"],
+                        None,
+                        format!(
+                            "plugins.{name}.verification: only oci:// plugins are verified, this has no effect"
+                        ),
+                    );
+                }
+                self.check_verification(
+                    path,
+                    content,
+                    &["plugins", name, "verification"],
+                    verification,
+                );
+            }
+
+            let Some(runtime_cfg) = &plugin_cfg.runtime_config else {
+                continue;
+            };
+
+            // Typed sources are only read when the plugin is loaded.
+            let mut env_vars: Vec<_> = runtime_cfg.env_vars.iter().flatten().collect();
+            env_vars.sort_by_key(|(key, _)| key.as_str());
+            for (key, value) in env_vars {
+                if let Err(e) = value.resolve() {
+                    self.report(
+                        path,
+                        content,
+                        Severity::Error,
+                        &["plugins", name, "runtime_config", "env_vars", key],
+                        None,
+                        format!("plugins.{name}.runtime_config.env_vars.{key}: {e:#}"),
+                    );
+                }
+            }
+
+            if let Some(memory_limit) = &runtime_cfg.memory_limit {
+                if let Err(e) = ByteSize::from_str(memory_limit) {
+                    self.report(
+                        path,
+                        content,
+                        Severity::Error,
+                        &["plugins", name, "runtime_config", "memory_limit"],
+                        None,
+                        format!(
+                            "plugins.{name}.runtime_config.memory_limit: can't parse '{memory_limit}': {e}"
+                        ),
+                    );
+                }
+            }
+
+            if runtime_cfg.use_client_roots == Some(true)
+                && runtime_cfg
+                    .allowed_paths
+                    .as_ref()
+                    .is_none_or(|allowed_paths| allowed_paths.is_empty())
+            {
+                self.report(
+                    path,
+                    content,
+                    Severity::Warning,
+                    &["plugins", name, "runtime_config", "use_client_roots"],
+                    None,
+                    format!(
+                        "plugins.{name}.runtime_config.use_client_roots: no allowed_paths to mount client roots within, the plugin won't see any"
+                    ),
+                );
+            }
+
+            for allowed_path in runtime_cfg.allowed_paths.iter().flatten() {
+                let allowed_path = &allowed_path.host;
+                if !Path::new(allowed_path).exists() {
+                    self.report(
+                        path,
+                        content,
+                        Severity::Error,
+                        &["plugins", name, "runtime_config", "allowed_paths"],
+                        Some(allowed_path),
+                        format!(
+                            "plugins.{name}.runtime_config.allowed_paths: {allowed_path} does not exist"
+                        ),
+                    );
+                }
+            }
+        }
+
+        // auths apply to plugins from every file.
+        let mut auth_urls: Vec<_> = source
+            .config
+            .auths
+            .iter()
+            .flat_map(|auths| auths.keys())
+            .collect();
+        auth_urls.sort_by_key(|url| url.as_str());
+        for url in auth_urls {
+            if !merged
+                .plugins
+                .values()
+                .any(|plugin_cfg| auth_applies(merged, url, plugin_cfg))
+            {
+                self.report(
+                    path,
+                    content,
+                    Severity::Warning,
+                    &["auths"],
+                    Some(url.as_str().trim_end_matches('/')),
+                    format!("auths: {url} matches no plugin URL"),
+                );
+            }
+        }
+    }
+
+    /// Loads every plugin on its own and checks `skip_tools` against what its
+    /// `describe` returns.
+    async fn check_plugins(&mut self, cli: &Cli, merged: &Config, sources: &[ConfigFile]) {
+        for (plugin_name, plugin_cfg) in sorted_plugins(merged) {
+            let name = plugin_name.as_str();
+            let Some(source) = sources
+                .iter()
+                .find(|source| merged.sources.get(plugin_name) == Some(&source.path))
+            else {
+                continue;
+            };
+            let (path, content) = (source.path.as_path(), source.content.as_str());
+
+            let single_plugin_config = Config {
+                plugins: HashMap::from([(plugin_name.clone(), plugin_cfg.clone())]),
+                ..merged.clone()
+            };
+            let tool_names = match PluginService::with_config(single_plugin_config, cli).await {
+                Ok(service) => service.describe_tool_names(plugin_name).await,
+                Err(e) => Err(e),
+            };
+            let tool_names = match tool_names {
+                Ok(tool_names) => tool_names,
+                Err(e) => {
+                    self.report(
+                        path,
+                        content,
+                        Severity::Error,
+                        &["plugins", name],
+                        None,
+                        format!("plugins.{name}: failed to load plugin: {e:#}"),
+                    );
+                    continue;
+                }
+            };
+
+            let skip_tools = plugin_cfg
+                .runtime_config
+                .as_ref()
+                .and_then(|rc| rc.skip_tools.as_ref());
+            for tool in skip_tools.into_iter().flatten() {
+                if !tool_names.contains(tool) {
+                    self.report(
+                        path,
+                        content,
+                        Severity::Warning,
+                        &["plugins", name, "runtime_config", "skip_tools"],
+                        Some(tool),
+                        format!(
+                            "plugins.{name}.runtime_config.skip_tools: {tool} matches no tool of the plugin (it has: {})",
+                            tool_names.join(", ")
+                        ),
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn sorted_plugins(config: &Config) -> Vec<(&PluginName, &PluginConfig)> {
+    let mut plugins: Vec<_> = config.plugins.iter().collect();
+    plugins.sort_by_key(|(plugin_name, _)| plugin_name.as_str());
+    plugins
+}
+
+/// Whether the `auths` entry `url` is used for `plugin_cfg`: for an `oci://`
+/// plugin the entry picked for the reference it's pulled from, mirror
+/// applied, otherwise any entry that is a prefix of its URL.
+fn auth_applies(config: &Config, url: &Url, plugin_cfg: &PluginConfig) -> bool {
+    let Some(image_reference) = plugin_cfg.url.as_str().strip_prefix("oci://") else {
+        return plugin_cfg.url.as_str().starts_with(url.as_str());
+    };
+    let Ok(reference) = Reference::try_from(config.pull_reference(image_reference)) else {
+        return false;
+    };
+    config
+        .auths
+        .as_ref()
+        .and_then(|auths| find_registry_auth(auths, &reference))
+        .is_some_and(|(found, _)| found == url)
+}
+
+fn position(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = offset - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+    (line, column)
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+/// Offset of the first whole-word occurrence of `token` at or after `from`.
+fn find_token(content: &str, from: usize, token: &str) -> Option<usize> {
+    if token.is_empty() {
+        return Some(from);
+    }
+    content[from..]
+        .match_indices(token)
+        .map(|(i, _)| from + i)
+        .find(|&i| {
+            let before = content[..i].chars().next_back();
+            let after = content[i + token.len()..].chars().next();
+            !before.is_some_and(is_identifier_char) && !after.is_some_and(is_identifier_char)
+        })
+}
+
+/// Finds where a value is in the config source by looking for each key (and
+/// then the list item) in turn. This works the same for YAML, JSON and TOML
+/// (where `[plugins.name.runtime_config]` headers contain the keys in order).
+/// Falls back to the closest parent that can be found.
+fn locate(content: &str, keys: &[&str], value: Option<&str>) -> Option<(usize, usize)> {
+    let find = |tokens: &mut dyn Iterator<Item = &str>| {
+        let mut start = 0;
+        let mut from = 0;
+        for token in tokens {
+            start = find_token(content, from, token)?;
+            from = start + token.len();
+        }
+        Some(position(content, start))
+    };
+
+    if let Some(position) = find(&mut keys.iter().copied().chain(value)) {
+        return Some(position);
+    }
+    (1..=keys.len())
+        .rev()
+        .find_map(|len| find(&mut keys[..len].iter().copied()))
+}
+
+type Position = Option<(usize, usize)>;
+
+/// Where in `content` the loader's `error` points: the position a parser
+/// reports, or the value an unresolved reference or a deserialization error
+/// names.
+fn error_position(content: &str, error: &anyhow::Error) -> Position {
+    if let Some(e) = error.downcast_ref::<InterpolationError>() {
+        let keys: Vec<&str> = e.keys.iter().map(String::as_str).collect();
+        return locate(content, &keys, Some(&e.value));
+    }
+    if let Some(e) = error.downcast_ref::<serde_path_to_error::Error<serde_json::Error>>() {
+        return locate(content, &path_keys(e.path()), None);
+    }
+    if let Some(e) = error.downcast_ref::<serde_json::Error>() {
+        return Some((e.line(), e.column()));
+    }
+    if let Some(e) = error.downcast_ref::<serde_yaml::Error>() {
+        return e.location().map(|l| (l.line(), l.column()));
+    }
+    if let Some(e) = error.downcast_ref::<toml::de::Error>() {
+        return e.span().map(|span| position(content, span.start));
+    }
+    None
+}
+
+/// The map keys of a deserialization error's path, for `locate`. The
+/// positions the parsers know are lost once the file is a `Value` that had
+/// its references and paths resolved.
+fn path_keys(path: &serde_path_to_error::Path) -> Vec<&str> {
+    path.iter()
+        .filter_map(|segment| match segment {
+            serde_path_to_error::Segment::Map { key } => Some(key.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Checks the config made of `files` (each followed by the files it
+/// includes): that every file parses, that every reference and typed secret
+/// resolves, that it matches the config structure, that plugin names are
+/// unique, and then semantic problems serde can't catch. With `deep` every
+/// plugin is also fetched and loaded.
+pub async fn validate_config(cli: &Cli, files: &[PathBuf], deep: bool) -> Vec<Diagnostic> {
+    let mut validator = Validator::default();
+    let mut sources = Vec::new();
+    let mut loaded = Vec::new();
+    for file in files {
+        read_config_tree(file, None, &mut loaded, &mut sources, &mut |e| {
+            validator.report_file_error(e)
+        });
+    }
+    if !validator.diagnostics.is_empty() {
+        return validator.diagnostics;
+    }
+
+    let merged = validator.merge(&sources);
+    for source in &sources {
+        validator.check_semantics(source, &merged);
+    }
+    if deep {
+        validator.check_plugins(cli, &merged, &sources).await;
+    }
+
+    let mut diagnostics = validator.diagnostics;
+    diagnostics.sort_by(|a, b| (&a.file, a.position).cmp(&(&b.file, b.position)));
+    diagnostics
+}
+
+/// `hyper-mcp config validate`
+pub async fn run(cli: &Cli, deep: bool) -> Result<()> {
+    let files = cli_config_files(cli)?;
+    for file in &files {
+        if !file.exists() {
+            return Err(anyhow::anyhow!(
+                "Config file not found at: {}",
+                file.display()
+            ));
+        }
+    }
+
+    let diagnostics = validate_config(cli, &files, deep).await;
+    for diagnostic in &diagnostics {
+        println!("{diagnostic}");
+    }
+
+    let errors = diagnostics
+        .iter()
+        .filter(|d| d.severity == Severity::Error)
+        .count();
+    let warnings = diagnostics.len() - errors;
+    if errors > 0 {
+        return Err(anyhow::anyhow!(
+            "Found {errors} error(s) and {warnings} warning(s)"
+        ));
+    }
+    eprintln!("Config is valid ({warnings} warning(s))");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    fn write_config(temp_dir: &TempDir, name: &str, content: &str) -> PathBuf {
+        let path = temp_dir.path().join(name);
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn get_test_wasm_path() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("examples/plugins/time/time.wasm")
+    }
+
+    #[test]
+    fn test_locate_yaml_json_toml() {
+        let yaml = "plugins:\n  time:\n    url: \"oci://x\"\n    runtime_config:\n      skip_tools:\n        - foo\n        - bar\n";
+        assert_eq!(
+            locate(
+                yaml,
+                &["plugins", "time", "runtime_config", "skip_tools"],
+                Some("bar")
+            ),
+            Some((7, 11))
+        );
+
+        let json = "{\n  \"plugins\": {\n    \"time\": {\n      \"url\": \"oci://x\",\n      \"runtime_config\": {\"memory_limit\": \"lots\"}\n    }\n  }\n}";
+        assert_eq!(
+            locate(
+                json,
+                &["plugins", "time", "runtime_config", "memory_limit"],
+                None
+            ),
+            Some((5, 27))
+        );
+
+        let toml = "[plugins.time]\nurl = \"oci://x\"\n\n[plugins.time.runtime_config]\nmemory_limit = \"lots\"\n";
+        assert_eq!(
+            locate(
+                toml,
+                &["plugins", "time", "runtime_config", "memory_limit"],
+                None
+            ),
+            Some((5, 1))
+        );
+
+        // Falls back to the closest parent that exists.
+        assert_eq!(
+            locate(
+                yaml,
+                &["plugins", "time", "runtime_config", "allowed_paths"],
+                Some("/x")
+            ),
+            Some((4, 5))
+        );
+        assert_eq!(locate(yaml, &["auths"], None), None);
+    }
+
+    #[tokio::test]
+    async fn test_validate_semantic_problems() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = write_config(
+            &temp_dir,
+            "config.yaml",
+            r#"auths:
+  "https://unused.example.com":
+    type: token
+    token: "abc"
+  "https://ghcr.io":
+    type: token
+    token: "abc"
+  "https://mirror.example.com":
+    type: token
+    token: "abc"
+registries:
+  mirrors:
+    "ghcr.io/tuananh/fs-plugin": "mirror.example.com/fs-plugin"
+plugins:
+  time:
+    url: "oci://ghcr.io/tuananh/time-plugin:latest"
+    runtime_config:
+      memory_limit: "a lot"
+      allowed_paths:
+        - "/definitely/not/a/real/path"
+  fs:
+    url: "oci://ghcr.io/tuananh/fs-plugin:latest"
+    runtime_config:
+      use_client_roots: true
+"#,
+        );
+        let cli = Cli::parse_from(["hyper-mcp"]);
+
+        let diagnostics = validate_config(&cli, std::slice::from_ref(&path), false).await;
+        // A registry entry covers all of its repositories, and one for a
+        // mirror is used by the plugins pulled from it.
+        assert_eq!(diagnostics.len(), 4, "{diagnostics:?}");
+
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(diagnostics[0].position, Some((2, 4)));
+        assert!(diagnostics[0].message.contains("matches no plugin URL"));
+
+        assert_eq!(diagnostics[1].severity, Severity::Error);
+        assert_eq!(diagnostics[1].position, Some((18, 7)));
+        assert!(diagnostics[1].message.contains("memory_limit"));
+
+        assert_eq!(diagnostics[2].severity, Severity::Error);
+        assert_eq!(diagnostics[2].position, Some((20, 12)));
+        assert!(diagnostics[2].message.contains("does not exist"));
+
+        assert_eq!(diagnostics[3].severity, Severity::Warning);
+        assert_eq!(diagnostics[3].position, Some((24, 7)));
+        assert!(diagnostics[3].message.contains("use_client_roots"));
+    }
+
+    #[tokio::test]
+    async fn test_validate_syntax_and_structure_errors() {
+        let temp_dir = TempDir::new().unwrap();
+        let cli = Cli::parse_from(["hyper-mcp"]);
+
+        let path = write_config(&temp_dir, "broken.json", "{\n  \"plugins\": {\n    oops\n}");
+        let diagnostics = validate_config(&cli, std::slice::from_ref(&path), false).await;
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].position, Some((3, 5)));
+
+        let path = write_config(
+            &temp_dir,
+            "bad_name.yaml",
+            "plugins:\n  bad-name:\n    url: \"file:///plugin.wasm\"\n",
+        );
+        let diagnostics = validate_config(&cli, std::slice::from_ref(&path), false).await;
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[0].position.map(|(line, _)| line), Some(2));
+
+        // A relative plugin path is resolved before deserializing, so it
+        // doesn't hide where the actual error is.
+        let path = write_config(
+            &temp_dir,
+            "relative.yaml",
+            "plugins:\n  time:\n    url: \"plugins/time.wasm\"\n    runtime_config:\n      allowed_hosts:\n        - \"example.com\"\n      memory_limit: 512\n",
+        );
+        let diagnostics = validate_config(&cli, std::slice::from_ref(&path), false).await;
+        assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+        assert_eq!(diagnostics[0].position, Some((7, 7)));
+        assert!(
+            diagnostics[0]
+                .message
+                .starts_with("plugins.time.runtime_config.memory_limit: invalid type")
+        );
+
+        let path = write_config(
+            &temp_dir,
+            "missing_env.toml",
+            "[plugins.time]\nurl = \"file:///plugin.wasm\"\n\n[plugins.time.runtime_config.env_vars]\nTOKEN = \"${env:HYPER_MCP_TEST_DEFINITELY_UNSET}\"\n",
+        );
+        let diagnostics = validate_config(&cli, std::slice::from_ref(&path), false).await;
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].position, Some((5, 10)));
+        assert!(
+            diagnostics[0]
+                .message
+                .starts_with("plugins.time.runtime_config.env_vars.TOKEN:")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_validate_env_var_sources() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = write_config(
+            &temp_dir,
+            "config.yaml",
+            r#"plugins:
+  gitlab:
+    url: "oci://ghcr.io/tuananh/gitlab-plugin:latest"
+    runtime_config:
+      env_vars:
+        URL: "https://gitlab.com/api/v4"
+        TOKEN:
+          env: HYPER_MCP_TEST_DEFINITELY_UNSET
+        KEY:
+          file: /definitely/missing/secret
+"#,
+        );
+        let cli = Cli::parse_from(["hyper-mcp"]);
+
+        let diagnostics = validate_config(&cli, std::slice::from_ref(&path), false).await;
+        assert_eq!(diagnostics.len(), 2, "{diagnostics:?}");
+
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[0].position, Some((7, 9)));
+        assert!(diagnostics[0].message.starts_with(
+            "plugins.gitlab.runtime_config.env_vars.TOKEN: Environment variable HYPER_MCP_TEST_DEFINITELY_UNSET is not set"
+        ));
+
+        assert_eq!(diagnostics[1].severity, Severity::Error);
+        assert_eq!(diagnostics[1].position, Some((9, 9)));
+        assert!(diagnostics[1].message.starts_with(
+            "plugins.gitlab.runtime_config.env_vars.KEY: Failed to read secret file /definitely/missing/secret"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_validate_verification() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = write_config(
+            &temp_dir,
+            "config.yaml",
+            r#"verification:
+  "ghcr.io/":
+    identities:
+      - subject: "[a-z"
+plugins:
+  local:
+    url: "file:///plugin.wasm"
+    verification:
+      skip: true
+  signed:
+    url: "oci://ghcr.io/org/signed:latest"
+    verification:
+      public_keys:
+        - "/definitely/not/cosign.pub"
+"#,
+        );
+        let cli = Cli::parse_from(["hyper-mcp"]);
+
+        let diagnostics = validate_config(&cli, std::slice::from_ref(&path), false).await;
+        assert_eq!(diagnostics.len(), 3, "{diagnostics:?}");
+
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[0].position, Some((3, 5)));
+        assert!(
+            diagnostics[0]
+                .message
+                .starts_with("verification.ghcr.io/.identities: Invalid signing identity pattern")
+        );
+
+        assert_eq!(diagnostics[1].severity, Severity::Warning);
+        assert_eq!(diagnostics[1].position, Some((8, 5)));
+        assert!(
+            diagnostics[1]
+                .message
+                .contains("only oci:// plugins are verified")
+        );
+
+        assert_eq!(diagnostics[2].severity, Severity::Error);
+        assert_eq!(diagnostics[2].position, Some((13, 7)));
+        assert!(diagnostics[2].message.starts_with(
+            "plugins.signed.verification.public_keys: Failed to read public key /definitely/not/cosign.pub"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_validate_registries() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = write_config(
+            &temp_dir,
+            "config.yaml",
+            r#"registries:
+  insecure:
+    - "localhost:5000"
+    - "http://localhost:5001"
+  ca_certs:
+    - "missing-ca.pem"
+plugins: {}
+"#,
+        );
+        let cli = Cli::parse_from(["hyper-mcp"]);
+
+        let diagnostics = validate_config(&cli, std::slice::from_ref(&path), false).await;
+        assert_eq!(diagnostics.len(), 2, "{diagnostics:?}");
+        assert_eq!(diagnostics[0].position, Some((4, 8)));
+        assert!(
+            diagnostics[0]
+                .message
+                .contains("must be a host or host:port")
+        );
+        assert_eq!(diagnostics[1].position, Some((5, 3)));
+        assert!(
+            diagnostics[1]
+                .message
+                .starts_with("registries.ca_certs: Failed to read CA certificate")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_validate_includes() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::create_dir(temp_dir.path().join("conf.d")).unwrap();
+        write_config(
+            &temp_dir,
+            "conf.d/team_a.yaml",
+            "plugins:\n  time:\n    url: \"oci://ghcr.io/tuananh/time-plugin:latest\"\n",
+        );
+        let team_b = write_config(
+            &temp_dir,
+            "conf.d/team_b.json",
+            "{\n  \"plugins\": {\n    \"time\": {\"url\": \"oci://ghcr.io/tuananh/time-plugin:v2\"},\n    \"qdrant\": {\"url\": \"oci://ghcr.io/tuananh/qdrant-plugin:latest\", \"runtime_config\": {\"memory_limit\": \"huge\"}}\n  }\n}\n",
+        );
+        let path = write_config(&temp_dir, "config.yaml", "include:\n  - conf.d\n");
+        let cli = Cli::parse_from(["hyper-mcp"]);
+
+        let diagnostics = validate_config(&cli, std::slice::from_ref(&path), false).await;
+        assert_eq!(diagnostics.len(), 2, "{diagnostics:?}");
+        assert!(diagnostics.iter().all(|d| d.file == team_b));
+
+        assert_eq!(diagnostics[0].position, Some((3, 6)));
+        assert!(diagnostics[0].message.contains("already defined in"));
+        assert!(diagnostics[0].message.contains("team_a.yaml"));
+
+        assert_eq!(diagnostics[1].position, Some((4, 89)));
+        assert!(diagnostics[1].message.contains("memory_limit"));
+    }
+
+    #[tokio::test]
+    async fn test_validate_conflicting_includes() {
+        let temp_dir = TempDir::new().unwrap();
+        let other = write_config(
+            &temp_dir,
+            "other.yaml",
+            "verification:\n  \"ghcr.io/\":\n    skip: true\nregistries:\n  mirrors:\n    \"docker.io/\": \"evil.example.com/\"\n",
+        );
+        let path = write_config(
+            &temp_dir,
+            "config.yaml",
+            "include:\n  - other.yaml\nverification:\n  \"ghcr.io/\":\n    skip: false\nregistries:\n  mirrors:\n    \"docker.io/\": \"mirror.example.com/\"\n",
+        );
+        let cli = Cli::parse_from(["hyper-mcp"]);
+
+        let diagnostics = validate_config(&cli, std::slice::from_ref(&path), false).await;
+        assert_eq!(diagnostics.len(), 2, "{diagnostics:?}");
+        assert!(diagnostics.iter().all(|d| d.file == other));
+
+        assert_eq!(diagnostics[0].position, Some((2, 4)));
+        assert_eq!(
+            diagnostics[0].message,
+            "verification.ghcr.io/: already set differently in another config file"
+        );
+
+        assert_eq!(diagnostics[1].position, Some((6, 6)));
+        assert_eq!(
+            diagnostics[1].message,
+            "registries.mirrors.docker.io/: already set differently in another config file"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_validate_deep_checks_skip_tools() {
+        let wasm_path = get_test_wasm_path();
+        if !wasm_path.exists() {
+            println!("Skipping test - WASM file not found at {wasm_path:?}");
+            return;
+        }
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = write_config(
+            &temp_dir,
+            "config.yaml",
+            &format!(
+                "plugins:\n  time:\n    url: \"file://{}\"\n    runtime_config:\n      skip_tools:\n        - time\n        - no_such_tool\n",
+                wasm_path.display()
+            ),
+        );
+        let cli = Cli::parse_from(["hyper-mcp"]);
+
+        assert!(
+            validate_config(&cli, std::slice::from_ref(&path), false)
+                .await
+                .is_empty()
+        );
+
+        let diagnostics = validate_config(&cli, std::slice::from_ref(&path), true).await;
+        assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(diagnostics[0].position, Some((7, 11)));
+        assert!(diagnostics[0].message.contains("no_such_tool"));
+    }
+}
@@ -0,0 +1,108 @@
+//! Just enough of the wasm binary format to find custom sections, where
+//! build tools leave metadata about a plugin.
+
+use anyhow::{Result, anyhow};
+
+const MAGIC: &[u8] = b"\0asm";
+const CUSTOM_SECTION_ID: u8 = 0;
+
+/// Reads an unsigned LEB128 number at `*offset` and advances past it.
+fn read_leb128(wasm: &[u8], offset: &mut usize) -> Result<usize> {
+    let mut value = 0usize;
+    for shift in (0..35).step_by(7) {
+        let byte = *wasm
+            .get(*offset)
+            .ok_or_else(|| anyhow!("Truncated wasm module"))?;
+        *offset += 1;
+        value |= usize::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(anyhow!("Invalid LEB128 number in wasm module"))
+}
+
+/// The contents of the first custom section called `name`, if there is one.
+pub fn custom_section<'a>(wasm: &'a [u8], name: &str) -> Result<Option<&'a [u8]>> {
+    if !wasm.starts_with(MAGIC) || wasm.len() < 8 {
+        return Err(anyhow!("Not a wasm module"));
+    }
+    let mut offset = 8;
+    while offset < wasm.len() {
+        let id = wasm[offset];
+        offset += 1;
+        let size = read_leb128(wasm, &mut offset)?;
+        let end = offset
+            .checked_add(size)
+            .filter(|end| *end <= wasm.len())
+            .ok_or_else(|| anyhow!("Truncated wasm module"))?;
+        if id == CUSTOM_SECTION_ID {
+            let mut name_offset = offset;
+            let name_len = read_leb128(wasm, &mut name_offset)?;
+            let section_name = wasm
+                .get(name_offset..name_offset + name_len)
+                .filter(|_| name_offset + name_len <= end)
+                .ok_or_else(|| anyhow!("Truncated wasm module"))?;
+            if section_name == name.as_bytes() {
+                return Ok(Some(&wasm[name_offset + name_len..end]));
+            }
+        }
+        offset = end;
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    fn leb128(mut value: usize) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                bytes.push(byte);
+                return bytes;
+            }
+            bytes.push(byte | 0x80);
+        }
+    }
+
+    /// An empty wasm module with the given custom sections.
+    pub(crate) fn module_with_sections(sections: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut wasm = [MAGIC, &[1, 0, 0, 0]].concat();
+        for (name, contents) in sections {
+            let mut section = leb128(name.len());
+            section.extend_from_slice(name.as_bytes());
+            section.extend_from_slice(contents);
+            wasm.push(CUSTOM_SECTION_ID);
+            wasm.extend(leb128(section.len()));
+            wasm.extend(section);
+        }
+        wasm
+    }
+
+    #[test]
+    fn test_custom_section() {
+        let contents = vec![7u8; 200];
+        let wasm = module_with_sections(&[("name", b"\0"), (".dep-v0", &contents)]);
+        assert_eq!(
+            custom_section(&wasm, ".dep-v0").unwrap(),
+            Some(contents.as_slice())
+        );
+        assert_eq!(custom_section(&wasm, "name").unwrap(), Some(&b"\0"[..]));
+        assert_eq!(custom_section(&wasm, "producers").unwrap(), None);
+
+        // A real plugin, built without cargo auditable.
+        let time_wasm = std::fs::read(
+            std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("examples/plugins/time/time.wasm"),
+        )
+        .unwrap();
+        assert_eq!(custom_section(&time_wasm, ".dep-v0").unwrap(), None);
+
+        assert!(custom_section(b"not wasm", ".dep-v0").is_err());
+        assert!(custom_section(&wasm[..wasm.len() - 1], ".dep-v0").is_err());
+    }
+}